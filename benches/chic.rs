@@ -0,0 +1,188 @@
+//! Benchmarks of every protocol stage and of the primitives below them.
+//!
+//! The Kyber level, hash backend and Rijndael implementation are fixed at compile time, so each
+//! build measures one combination; the benchmark ids carry its name so that criterion keeps the
+//! results of different builds apart. For instance:
+//!
+//!     cargo bench --features bench-internals
+//!     cargo bench --features bench-internals,sha2
+//!     cargo bench --features bench-internals,sha2,small_sha
+//!     cargo bench --features bench-internals,use_kyber1024
+//!
+//! Before measuring time, the peak stack usage of every stage is printed.
+
+use std::hint::black_box;
+
+use chic_rust::internals::*;
+use chic_rust::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::rngs::ThreadRng;
+use rand::RngCore;
+
+fn keypair_func(rng: &mut ThreadRng) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError> {
+    let keypair = pqc_kyber::keypair(rng).unwrap();
+    Ok((keypair.public, keypair.secret))
+}
+
+fn encapsulate_func(pk: &[u8;KYBER_PUBLICKEYBYTES], rng: &mut ThreadRng) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError> {
+    Ok(pqc_kyber::encapsulate(pk, rng).unwrap())
+}
+
+fn decapsulate_func(ct: &[u8;KYBER_CIPHERTEXTBYTES], sk: &[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError> {
+    Ok(pqc_kyber::decapsulate(ct, sk).unwrap())
+}
+
+fn kyber_level() -> String {
+    format!("kyber{}", KYBER_K * 256)
+}
+
+const STACK_PAINT_BYTES: usize = 256 * 1024;
+const STACK_PAINT: u8 = 0xa5;
+
+/// Fills STACK_PAINT_BYTES of fresh stack below the caller and returns their lowest address
+#[inline(never)]
+fn paint_stack() -> usize {
+    let mut painted = [STACK_PAINT; STACK_PAINT_BYTES];
+    black_box(&mut painted);
+    painted.as_ptr() as usize
+}
+
+/// Number of painted bytes that were overwritten since paint_stack
+#[inline(never)]
+fn painted_bytes_used(low: usize) -> usize {
+    let low = low as *const u8;
+    let mut untouched = 0;
+    // SAFETY: the painted region lies within this thread's stack, below the current frame,
+    // and is only read
+    while untouched < STACK_PAINT_BYTES && unsafe { core::ptr::read_volatile(low.add(untouched)) } == STACK_PAINT {
+        untouched += 1;
+    }
+    STACK_PAINT_BYTES - untouched
+}
+
+/// Peak stack usage of f, in bytes, measured in a fresh thread
+fn stack_usage<F: FnOnce() + Send>(f: F) -> usize {
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let low = paint_stack();
+            f();
+            painted_bytes_used(low)
+        }).join().unwrap()
+    })
+}
+
+fn report_stack_usage() {
+    let mut rng = rand::thread_rng();
+    let mut pw = [0u8;KYBER_SYMBYTES];
+    rng.fill_bytes(&mut pw);
+    let (enc_pk, pk, sk) = pake_init_start(&pw, &mut rng, keypair_func).unwrap();
+    let (sid, msg1) = pake_split_msg1(&enc_pk);
+    let (msg2, _, _) = pake_resp(&sid, &msg1, &pw, &mut rng, encapsulate_func).unwrap();
+
+    let level = kyber_level();
+    let usage = [
+        ("hic_eval", stack_usage(|| { let mut icc = [0u8;KYBER_PUBLICKEYBYTES]; hic_eval(&mut icc, &pk, &pw, &sid).unwrap(); black_box(icc); })),
+        ("hic_inv", stack_usage(|| { let mut out = [0u8;KYBER_PUBLICKEYBYTES]; hic_inv(&mut out, &msg1, &pw, &sid).unwrap(); black_box(out); })),
+        ("init_start", stack_usage(|| { black_box(pake_init_start(&pw, &mut rand::thread_rng(), keypair_func).unwrap()); })),
+        ("resp", stack_usage(|| { black_box(pake_resp(&sid, &msg1, &pw, &mut rand::thread_rng(), encapsulate_func).unwrap()); })),
+        ("init_end", stack_usage(|| { black_box(pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func).unwrap()); })),
+    ];
+    for (stage, bytes) in usage {
+        println!("stack usage {}/{}/{}: {} bytes", stage, level, HASH_BACKEND, bytes);
+    }
+}
+
+fn bench_primitives(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let mut seed = [0u8;KYBER_SYMBYTES];
+    rng.fill_bytes(&mut seed);
+
+    let mut group = c.benchmark_group("hash");
+    for len in [2*KYBER_SYMBYTES, KYBER_PUBLICKEYBYTES] {
+        let mut input = vec![0u8;len];
+        rng.fill_bytes(&mut input);
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_with_input(BenchmarkId::new(format!("hash_h/{}", HASH_BACKEND), len), &input, |b, input| {
+            let mut out = [0u8;32];
+            b.iter(|| hash_h(&mut out, black_box(input), input.len()))
+        });
+        group.bench_with_input(BenchmarkId::new(format!("hash_g/{}", HASH_BACKEND), len), &input, |b, input| {
+            let mut out = [0u8;64];
+            b.iter(|| hash_g(&mut out, black_box(input), input.len()))
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("ic256");
+    let mut block = [0u8;32];
+    rng.fill_bytes(&mut block);
+    group.bench_function(BenchmarkId::new("ic256_enc", ic256_implementation()), |b| {
+        b.iter(|| ic256_enc(black_box(&mut block), &seed))
+    });
+    group.bench_function(BenchmarkId::new("ic256_dec", ic256_implementation()), |b| {
+        b.iter(|| ic256_dec(black_box(&mut block), &seed))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("hic");
+    let level = kyber_level();
+    group.bench_function(BenchmarkId::new("gen_vector", &level), |b| {
+        let mut v = Polyvec::new();
+        b.iter(|| gen_vector(black_box(&mut v), &seed))
+    });
+    let (pk, _) = keypair_func(&mut rng).unwrap();
+    let sid = seed;
+    let mut pw = [0u8;KYBER_SYMBYTES];
+    rng.fill_bytes(&mut pw);
+    let mut icc = [0u8;KYBER_PUBLICKEYBYTES];
+    hic_eval(&mut icc, &pk, &pw, &sid).unwrap();
+    group.bench_function(BenchmarkId::new("hic_eval", &level), |b| {
+        let mut out = [0u8;KYBER_PUBLICKEYBYTES];
+        b.iter(|| hic_eval(black_box(&mut out), &pk, &pw, &sid).unwrap())
+    });
+    group.bench_function(BenchmarkId::new("hic_inv", &level), |b| {
+        let mut out = [0u8;KYBER_PUBLICKEYBYTES];
+        b.iter(|| hic_inv(black_box(&mut out), &icc, &pw, &sid).unwrap())
+    });
+    group.finish();
+}
+
+fn bench_protocol(c: &mut Criterion) {
+    report_stack_usage();
+
+    let mut rng = rand::thread_rng();
+    let mut pw = [0u8;KYBER_SYMBYTES];
+    rng.fill_bytes(&mut pw);
+    let (enc_pk, pk, sk) = pake_init_start(&pw, &mut rng, keypair_func).unwrap();
+    let (sid, msg1) = pake_split_msg1(&enc_pk);
+    let (msg2, _, _) = pake_resp(&sid, &msg1, &pw, &mut rng, encapsulate_func).unwrap();
+
+    let level = kyber_level();
+    let mut group = c.benchmark_group("chic");
+    group.bench_function(BenchmarkId::new("init_start", &level), |b| {
+        b.iter(|| pake_init_start(&pw, &mut rng, keypair_func).unwrap())
+    });
+    group.bench_function(BenchmarkId::new("resp", &level), |b| {
+        b.iter(|| pake_resp(&sid, black_box(&msg1), &pw, &mut rng, encapsulate_func).unwrap())
+    });
+    group.bench_function(BenchmarkId::new("init_end", &level), |b| {
+        b.iter(|| pake_init_end(black_box(&msg2), &sid, &msg1, &pk, &sk, decapsulate_func).unwrap())
+    });
+
+    // Elements per second is handshakes per second
+    group.throughput(Throughput::Elements(1));
+    group.bench_function(BenchmarkId::new("handshake", &level), |b| {
+        b.iter(|| {
+            let (enc_pk, pk, sk) = pake_init_start(&pw, &mut rng, keypair_func).unwrap();
+            let (sid, msg1) = pake_split_msg1(&enc_pk);
+            let (msg2, _, tag_b) = pake_resp(&sid, &msg1, &pw, &mut rng, encapsulate_func).unwrap();
+            let (key, tag_a) = pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func).unwrap();
+            pake_resp_confirm(&tag_b, &tag_a).unwrap();
+            key
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_primitives, bench_protocol);
+criterion_main!(benches);
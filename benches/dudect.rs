@@ -0,0 +1,178 @@
+//! dudect-style timing leak detection (Reparaz, Balasch, Verbauwhede, DATE 2017).
+//!
+//! Every case times an operation under two input classes, interleaved in random order, and applies
+//! Welch's t-test to the two timing distributions, on all measurements and with the slowest ones
+//! cropped. |t| above THRESHOLD means the timing depends on the class. Cases that are claimed to be
+//! constant time (verify, cmov and the implicit rejection path of init_end) make the run fail when
+//! they exceed it, as does ic256 unless it runs the table-based Rijndael code. The others are
+//! reported for information: the rejection sampling of hic_eval and hic_inv runs for a number of
+//! rounds that depends on the password.
+//!
+//!     cargo bench --features bench-internals --bench dudect
+//!     CHIC_DUDECT_SAMPLES=1000000 cargo bench --features bench-internals --bench dudect
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use chic_rust::internals::*;
+use chic_rust::*;
+use rand::rngs::ThreadRng;
+use rand::{Rng, RngCore};
+
+/// dudect's threshold for "probably not constant time"
+const THRESHOLD: f64 = 4.5;
+const DEFAULT_SAMPLES: usize = 100_000;
+/// Percentiles above which measurements are cropped
+const CROP_PERCENTILES: [f64;3] = [0.5, 0.75, 0.9];
+
+/// Running mean and variance (Welford)
+#[derive(Default)]
+struct Moments {
+    n: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Moments {
+    fn push(&mut self, x: f64) {
+        self.n += 1.0;
+        let delta = x - self.mean;
+        self.mean += delta / self.n;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    fn variance(&self) -> f64 {
+        self.m2 / (self.n - 1.0)
+    }
+}
+
+/// Welch's t statistic of the measurements of class 0 against class 1, keeping those below limit
+fn welch_t(measurements: &[(bool, u64)], limit: u64) -> f64 {
+    let mut classes = [Moments::default(), Moments::default()];
+    for &(class, t) in measurements.iter().filter(|(_, t)| *t <= limit) {
+        classes[class as usize].push(t as f64);
+    }
+    let [a, b] = classes;
+    if a.n < 2.0 || b.n < 2.0 {
+        return 0.0;
+    }
+    (a.mean - b.mean) / (a.variance() / a.n + b.variance() / b.n).sqrt()
+}
+
+fn samples() -> usize {
+    std::env::var("CHIC_DUDECT_SAMPLES").ok().and_then(|s| s.parse().ok()).unwrap_or(DEFAULT_SAMPLES)
+}
+
+/// Times op on inputs of both classes and reports the largest |t| over all croppings. Returns false
+/// if a case claimed constant time exceeds THRESHOLD. input(false, rng) is the fixed class,
+/// input(true, rng) the random one. Each measurement covers inner calls, so that fast operations
+/// exceed the timer resolution.
+fn run_case<I, G, F>(name: &str, constant_time: bool, inner: usize, mut input: G, mut op: F) -> bool
+where
+    G: FnMut(bool, &mut ThreadRng) -> I,
+    F: FnMut(&I)
+{
+    let mut rng = rand::thread_rng();
+    let n = samples();
+    let inputs: Vec<(bool, I)> = (0..n).map(|_| {
+        let class = rng.gen::<bool>();
+        (class, input(class, &mut rng))
+    }).collect();
+
+    let mut measurements = Vec::with_capacity(n);
+    for (class, i) in &inputs {
+        let start = Instant::now();
+        for _ in 0..inner {
+            op(black_box(i));
+        }
+        measurements.push((*class, start.elapsed().as_nanos() as u64));
+    }
+
+    let mut sorted: Vec<u64> = measurements.iter().map(|(_, t)| *t).collect();
+    sorted.sort_unstable();
+    let mut t_max = welch_t(&measurements, u64::MAX).abs();
+    for p in CROP_PERCENTILES {
+        let limit = sorted[((n as f64 * p) as usize).min(n - 1)];
+        t_max = t_max.max(welch_t(&measurements, limit).abs());
+    }
+
+    let leak = t_max > THRESHOLD;
+    let verdict = match (leak, constant_time) {
+        (false, _) => "ok",
+        (true, true) => "LEAK",
+        (true, false) => "leak (not claimed constant time)",
+    };
+    println!("{:<32} samples {:>8}  max |t| {:>8.2}  {}", name, n, t_max, verdict);
+    !(leak && constant_time)
+}
+
+fn random<const N: usize>(rng: &mut ThreadRng) -> [u8;N] {
+    let mut x = [0u8;N];
+    rng.fill_bytes(&mut x);
+    x
+}
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    let sid: [u8;KYBER_SYMBYTES] = random(&mut rng);
+    let fixed_pw: [u8;KYBER_SYMBYTES] = random(&mut rng);
+    let pk = pqc_kyber::keypair(&mut rng).unwrap().public;
+    let mut icc = [0u8;KYBER_PUBLICKEYBYTES];
+    hic_eval(&mut icc, &pk, &fixed_pw, &sid).unwrap();
+    let mut ok = true;
+
+    // Fixed password against random password
+    let pw_class = |random_class: bool, rng: &mut ThreadRng| if random_class { random(rng) } else { fixed_pw };
+    ok &= run_case("hic_eval/password", false, 1, pw_class, |pw: &[u8;KYBER_SYMBYTES]| {
+        let mut out = [0u8;KYBER_PUBLICKEYBYTES];
+        black_box(hic_eval(&mut out, &pk, pw, &sid)).unwrap();
+    });
+    ok &= run_case("hic_inv/password", false, 1, pw_class, |pw: &[u8;KYBER_SYMBYTES]| {
+        let mut out = [0u8;KYBER_PUBLICKEYBYTES];
+        black_box(hic_inv(&mut out, &icc, pw, &sid)).unwrap();
+    });
+
+    // Fixed block against random block, under one key
+    let key: [u8;32] = random(&mut rng);
+    let fixed_block: [u8;32] = random(&mut rng);
+    ok &= run_case(&format!("ic256_enc/{}/block", ic256_implementation()), ic256_implementation() != "rijndael-table", 4, |random_class, rng| if random_class { random(rng) } else { fixed_block }, |block: &[u8;32]| {
+        let mut b = *block;
+        ic256_enc(&mut b, &key);
+        black_box(b);
+    });
+
+    // Valid against invalid tag
+    let tag: [u8;KYBER_SYMBYTES] = random(&mut rng);
+    ok &= run_case("verify/tag", true, 64, |random_class, rng| if random_class { random(rng) } else { tag }, |received: &[u8;KYBER_SYMBYTES]| {
+        black_box(verify(black_box(&tag), received, KYBER_SYMBYTES));
+    });
+
+    // Condition bit 0 against 1
+    let source: [u8;KYBER_SYMBYTES] = random(&mut rng);
+    ok &= run_case("cmov/condition", true, 64, |random_class, _| random_class as u8, |b: &u8| {
+        let mut r = [0u8;KYBER_SYMBYTES];
+        cmov(&mut r, black_box(&source), KYBER_SYMBYTES, *b);
+        black_box(r);
+    });
+
+    // init_end with implicit rejection: valid responder tag against modified tag
+    let keypair_func = |rng: &mut ThreadRng| {
+        let keypair = pqc_kyber::keypair(rng).unwrap();
+        Ok((keypair.public, keypair.secret))
+    };
+    let encapsulate_func = |pk: &[u8;KYBER_PUBLICKEYBYTES], rng: &mut ThreadRng| Ok(pqc_kyber::encapsulate(pk, rng).unwrap());
+    let decapsulate_func = |ct: &[u8;KYBER_CIPHERTEXTBYTES], sk: &[u8;KYBER_SECRETKEYBYTES]| Ok(pqc_kyber::decapsulate(ct, sk).unwrap());
+    let (enc_pk, pk, sk) = pake_init_start(&fixed_pw, &mut rng, keypair_func).unwrap();
+    let (sid, msg1) = pake_split_msg1(&enc_pk);
+    let (msg2, _, _) = pake_resp(&sid, &msg1, &fixed_pw, &mut rng, encapsulate_func).unwrap();
+    let mut bad_msg2 = msg2;
+    bad_msg2[0] ^= 1;
+    let z: [u8;KYBER_SYMBYTES] = random(&mut rng);
+    ok &= run_case("init_end_implicit/tag", true, 1, |invalid, _| if invalid { bad_msg2 } else { msg2 }, |m: &[u8;MSG2_LEN]| {
+        black_box(pake_init_end_implicit(m, &sid, &msg1, &pk, &sk, &z, decapsulate_func)).unwrap();
+    });
+
+    if !ok {
+        std::process::exit(1);
+    }
+}
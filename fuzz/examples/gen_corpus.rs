@@ -0,0 +1,40 @@
+//! Writes a seed corpus of honest transcripts for every fuzz target to fuzz/corpus/<target>.
+//!
+//!     cargo run --example gen_corpus
+
+use std::fs;
+use std::path::Path;
+
+use chic_rust::*;
+use chic_rust_fuzz::*;
+use rand::RngCore;
+
+const SEEDS: usize = 8;
+
+fn write(target: &str, i: usize, len: usize, parts: &[&[u8]]) {
+    let input = parts.concat();
+    assert_eq!(input.len(), len);
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus").join(target);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(format!("honest-{}", i)), input).unwrap();
+}
+
+fn main() {
+    let mut rng = rand::thread_rng();
+    for i in 0..SEEDS {
+        let mut pw = [0u8;KYBER_SYMBYTES];
+        rng.fill_bytes(&mut pw);
+
+        // An honest run, with the initiator the init_end target uses
+        let (sid, msg1, _, _) = initiator(&pw);
+        let (msg2, _, _) = pake_resp(&sid, &msg1, &pw, &mut rng, |pk, rng| Ok(pqc_kyber::encapsulate(pk, rng).unwrap())).unwrap();
+
+        write("hic_inv", i, HIC_INV_INPUT, &[&pw, &sid, &msg1]);
+        write("resp", i, RESP_INPUT, &[&pw, &sid, &msg1]);
+        write("init_end", i, INIT_END_INPUT, &[&pw, &msg2]);
+
+        let mut key_block = [0u8;RIJNDAEL_INPUT];
+        rng.fill_bytes(&mut key_block);
+        write("rijndael", i, RIJNDAEL_INPUT, &[&key_block]);
+    }
+}
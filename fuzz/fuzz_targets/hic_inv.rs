@@ -0,0 +1,20 @@
+#![no_main]
+
+use chic_rust::*;
+use chic_rust_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+// hic_inv on arbitrary bytes, and hic_eval must map every accepted input back to itself
+fuzz_target!(|data: &[u8]| {
+    let mut data = data;
+    let (Some(pw), Some(sid), Some(icc)) = (take::<KYBER_SYMBYTES>(&mut data), take::<KYBER_SYMBYTES>(&mut data), take::<KYBER_PUBLICKEYBYTES>(&mut data)) else {
+        return;
+    };
+
+    let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
+    if hic_inv(&mut pk, &icc, &pw, &sid).is_ok() {
+        let mut icc2 = [0u8;KYBER_PUBLICKEYBYTES];
+        hic_eval(&mut icc2, &pk, &pw, &sid).expect("hic_eval rejected the output of hic_inv");
+        assert_eq!(icc, icc2);
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use chic_rust::*;
+use chic_rust_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+// Initiator processing of an arbitrary msg2
+fuzz_target!(|data: &[u8]| {
+    let mut data = data;
+    let (Some(pw), Some(msg2)) = (take::<KYBER_SYMBYTES>(&mut data), take::<MSG2_LEN>(&mut data)) else {
+        return;
+    };
+
+    let (sid, msg1, pk, sk) = initiator(&pw);
+    let _ = pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func);
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use chic_rust::*;
+use chic_rust_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+// Responder processing of an arbitrary msg1
+fuzz_target!(|data: &[u8]| {
+    let mut data = data;
+    let (Some(pw), Some(sid), Some(msg1)) = (take::<KYBER_SYMBYTES>(&mut data), take::<KYBER_SYMBYTES>(&mut data), take::<MSG1_LEN>(&mut data)) else {
+        return;
+    };
+
+    let _ = pake_resp(&sid, &msg1, &pw, &mut fixed_rng(), encapsulate_func);
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+use chic_rust::internals::{ic256_dec, ic256_enc};
+use chic_rust_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+// ic256_dec inverts ic256_enc and the other way around
+fuzz_target!(|data: &[u8]| {
+    let mut data = data;
+    let (Some(key), Some(block)) = (take::<32>(&mut data), take::<32>(&mut data)) else {
+        return;
+    };
+
+    let mut b = block;
+    ic256_enc(&mut b, &key);
+    ic256_dec(&mut b, &key);
+    assert_eq!(b, block);
+
+    ic256_dec(&mut b, &key);
+    ic256_enc(&mut b, &key);
+    assert_eq!(b, block);
+});
@@ -0,0 +1,54 @@
+//! Input layouts shared by the fuzz targets and the corpus generator.
+//!
+//! All randomness comes from a StdRng with a fixed seed, so every crash reproduces from its input.
+
+use chic_rust::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// hic_inv input: pw || sid || icc
+pub const HIC_INV_INPUT: usize = 2*KYBER_SYMBYTES + KYBER_PUBLICKEYBYTES;
+/// resp input: pw || sid || msg1
+pub const RESP_INPUT: usize = 2*KYBER_SYMBYTES + MSG1_LEN;
+/// init_end input: pw || msg2, against the initiator state of initiator(pw)
+pub const INIT_END_INPUT: usize = KYBER_SYMBYTES + MSG2_LEN;
+/// rijndael input: key || block
+pub const RIJNDAEL_INPUT: usize = 64;
+
+pub fn fixed_rng() -> StdRng {
+    StdRng::seed_from_u64(0)
+}
+
+pub fn keypair_func(rng: &mut StdRng) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError> {
+    let keypair = pqc_kyber::keypair(rng).unwrap();
+    Ok((keypair.public, keypair.secret))
+}
+
+pub fn encapsulate_func(pk: &[u8;KYBER_PUBLICKEYBYTES], rng: &mut StdRng) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError> {
+    // Arbitrary bytes are a valid Kyber public key
+    Ok(pqc_kyber::encapsulate(pk, rng).unwrap())
+}
+
+pub fn decapsulate_func(ct: &[u8;KYBER_CIPHERTEXTBYTES], sk: &[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError> {
+    // Kyber decapsulation rejects implicitly, it never fails on a ciphertext of the right size
+    Ok(pqc_kyber::decapsulate(ct, sk).unwrap())
+}
+
+/// Initiator state used by the init_end target: sid, msg1, pk, sk
+pub fn initiator(pw: &[u8;KYBER_SYMBYTES]) -> ([u8;KYBER_SYMBYTES], [u8;MSG1_LEN], [u8;KYBER_PUBLICKEYBYTES], [u8;KYBER_SECRETKEYBYTES]) {
+    let mut rng = fixed_rng();
+    let (enc_pk, pk, sk) = pake_init_start(pw, &mut rng, keypair_func).unwrap();
+    let (sid, msg1) = pake_split_msg1(&enc_pk);
+    (sid, msg1, pk, sk)
+}
+
+/// Splits data into consecutive fixed-size arrays; None if data is too short
+pub fn take<const N: usize>(data: &mut &[u8]) -> Option<[u8;N]> {
+    if data.len() < N {
+        return None;
+    }
+    let mut out = [0u8;N];
+    out.copy_from_slice(&data[..N]);
+    *data = &data[N..];
+    Some(out)
+}
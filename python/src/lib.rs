@@ -0,0 +1,157 @@
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use chic_rust::{
+    KYBER_CIPHERTEXTBYTES, KYBER_PUBLICKEYBYTES, KYBER_SECRETKEYBYTES, KYBER_SYMBYTES, MSG1_LEN,
+    MSG2_LEN,
+};
+
+create_exception!(chic, ChicError, PyException);
+create_exception!(chic, AuthenticationError, ChicError);
+
+/// Copies a Python `bytes` argument into a fixed size array, raising `ValueError` on a length mismatch
+fn to_array<const N: usize>(name: &str, data: &[u8]) -> PyResult<[u8; N]> {
+    data.try_into().map_err(|_| {
+        PyValueError::new_err(format!("{} must be {} bytes, got {}", name, N, data.len()))
+    })
+}
+
+fn pake_err(e: chic_rust::PakeError) -> PyErr {
+    match e {
+        chic_rust::PakeError::AuthenticationFailure => AuthenticationError::new_err(format!("{}", e)),
+        chic_rust::PakeError::Kem(ref kem) => ChicError::new_err(format!("{}: {}", e, kem)),
+        e => ChicError::new_err(format!("{}", e)),
+    }
+}
+
+/// Wraps a failure of the Kyber implementation for the closures passed to the protocol API
+fn kem_err(e: pqc_kyber::KyberError) -> chic_rust::PakeError {
+    chic_rust::PakeError::Kem(match e {
+        pqc_kyber::KyberError::InvalidInput => chic_rust::KyberError::InvalidInput,
+        pqc_kyber::KyberError::Decapsulation => chic_rust::KyberError::Decapsulation,
+        pqc_kyber::KyberError::RandomBytesGeneration => chic_rust::KyberError::RandomBytesGeneration,
+    })
+}
+
+/// init_start(pw, channel_binding=b"") -> (msg1, pk, sk)
+///
+/// First stage of the initiator. `msg1` is the sid followed by the encrypted public key,
+/// exactly as sent on the wire; `pk` and `sk` are the initiator state needed by `init_end`.
+/// `channel_binding` (e.g. a TLS exporter value) must be the same in all three stages.
+#[pyfunction]
+#[pyo3(signature = (pw, channel_binding = &[] as &[u8]))]
+fn init_start<'py>(
+    py: Python<'py>,
+    pw: &[u8],
+    channel_binding: &[u8],
+) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let pw = to_array::<KYBER_SYMBYTES>("pw", pw)?;
+    let mut rng = rand::thread_rng();
+    let (msg1, pk, sk) = chic_rust::pake_init_start_bound(&pw, channel_binding, &mut rng, |rng| {
+        let keys = pqc_kyber::keypair(rng).map_err(kem_err)?;
+        Ok((keys.public, keys.secret))
+    })
+    .map_err(pake_err)?;
+    Ok((
+        PyBytes::new_bound(py, &msg1),
+        PyBytes::new_bound(py, &pk),
+        PyBytes::new_bound(py, &sk),
+    ))
+}
+
+/// resp(sid, msg1, pw, channel_binding=b"") -> (msg2, key, init_tag)
+///
+/// Responder stage. `msg1` is the encrypted public key without the leading sid. `init_tag` is the
+/// key confirmation tag the responder expects back from the initiator.
+#[pyfunction]
+#[pyo3(signature = (sid, msg1, pw, channel_binding = &[] as &[u8]))]
+fn resp<'py>(
+    py: Python<'py>,
+    sid: &[u8],
+    msg1: &[u8],
+    pw: &[u8],
+    channel_binding: &[u8],
+) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let sid = to_array::<KYBER_SYMBYTES>("sid", sid)?;
+    let msg1 = to_array::<MSG1_LEN>("msg1", msg1)?;
+    let pw = to_array::<KYBER_SYMBYTES>("pw", pw)?;
+    let mut rng = rand::thread_rng();
+    let (msg2, key, init_tag) = chic_rust::pake_resp_bound(&sid, &msg1, &pw, channel_binding, &mut rng, |pk, rng| {
+        pqc_kyber::encapsulate(pk, rng).map_err(kem_err)
+    })
+    .map_err(pake_err)?;
+    Ok((
+        PyBytes::new_bound(py, &msg2),
+        PyBytes::new_bound(py, &key),
+        PyBytes::new_bound(py, &init_tag),
+    ))
+}
+
+/// init_end(msg2, sid, msg1, pk, sk, channel_binding=b"") -> (key, init_tag)
+///
+/// Last stage of the initiator. Raises `AuthenticationError` if the responder's tag does not verify.
+#[pyfunction]
+#[pyo3(signature = (msg2, sid, msg1, pk, sk, channel_binding = &[] as &[u8]))]
+fn init_end<'py>(
+    py: Python<'py>,
+    msg2: &[u8],
+    sid: &[u8],
+    msg1: &[u8],
+    pk: &[u8],
+    sk: &[u8],
+    channel_binding: &[u8],
+) -> PyResult<(Bound<'py, PyBytes>, Bound<'py, PyBytes>)> {
+    let msg2 = to_array::<MSG2_LEN>("msg2", msg2)?;
+    let sid = to_array::<KYBER_SYMBYTES>("sid", sid)?;
+    let msg1 = to_array::<MSG1_LEN>("msg1", msg1)?;
+    let pk = to_array::<KYBER_PUBLICKEYBYTES>("pk", pk)?;
+    let sk = to_array::<KYBER_SECRETKEYBYTES>("sk", sk)?;
+    let (key, init_tag) = chic_rust::pake_init_end_bound(&msg2, &sid, &msg1, &pk, &sk, channel_binding, |ct, sk| {
+        pqc_kyber::decapsulate(ct, sk).map_err(kem_err)
+    })
+    .map_err(pake_err)?;
+    Ok((PyBytes::new_bound(py, &key), PyBytes::new_bound(py, &init_tag)))
+}
+
+/// hic_eval(pk, pw, sid) -> icc
+#[pyfunction]
+fn hic_eval<'py>(py: Python<'py>, pk: &[u8], pw: &[u8], sid: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let pk = to_array::<KYBER_PUBLICKEYBYTES>("pk", pk)?;
+    let pw = to_array::<KYBER_SYMBYTES>("pw", pw)?;
+    let sid = to_array::<KYBER_SYMBYTES>("sid", sid)?;
+    let mut icc = [0u8; KYBER_PUBLICKEYBYTES];
+    chic_rust::hic_eval(&mut icc, &pk, &pw, &sid).map_err(pake_err)?;
+    Ok(PyBytes::new_bound(py, &icc))
+}
+
+/// hic_inv(icc, pw, sid) -> pk
+#[pyfunction]
+fn hic_inv<'py>(py: Python<'py>, icc: &[u8], pw: &[u8], sid: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+    let icc = to_array::<KYBER_PUBLICKEYBYTES>("icc", icc)?;
+    let pw = to_array::<KYBER_SYMBYTES>("pw", pw)?;
+    let sid = to_array::<KYBER_SYMBYTES>("sid", sid)?;
+    let mut pk = [0u8; KYBER_PUBLICKEYBYTES];
+    chic_rust::hic_inv(&mut pk, &icc, &pw, &sid).map_err(pake_err)?;
+    Ok(PyBytes::new_bound(py, &pk))
+}
+
+#[pymodule]
+fn chic(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("ChicError", m.py().get_type_bound::<ChicError>())?;
+    m.add("AuthenticationError", m.py().get_type_bound::<AuthenticationError>())?;
+    m.add("KYBER_K", chic_rust::KYBER_K)?;
+    m.add("KYBER_SYMBYTES", KYBER_SYMBYTES)?;
+    m.add("KYBER_PUBLICKEYBYTES", KYBER_PUBLICKEYBYTES)?;
+    m.add("KYBER_SECRETKEYBYTES", KYBER_SECRETKEYBYTES)?;
+    m.add("KYBER_CIPHERTEXTBYTES", KYBER_CIPHERTEXTBYTES)?;
+    m.add("MSG1_LEN", MSG1_LEN)?;
+    m.add("MSG2_LEN", MSG2_LEN)?;
+    m.add_function(wrap_pyfunction!(init_start, m)?)?;
+    m.add_function(wrap_pyfunction!(resp, m)?)?;
+    m.add_function(wrap_pyfunction!(init_end, m)?)?;
+    m.add_function(wrap_pyfunction!(hic_eval, m)?)?;
+    m.add_function(wrap_pyfunction!(hic_inv, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,65 @@
+import os
+
+import pytest
+
+import chic
+
+
+def handshake(pw_a, pw_b):
+    msg, pk, sk = chic.init_start(pw_a)
+    sid, msg1 = msg[: chic.KYBER_SYMBYTES], msg[chic.KYBER_SYMBYTES :]
+    msg2, key_b, tag_b = chic.resp(sid, msg1, pw_b)
+    key_a, tag_a = chic.init_end(msg2, sid, msg1, pk, sk)
+    return key_a, tag_a, key_b, tag_b
+
+
+def test_honest_run():
+    pw = os.urandom(chic.KYBER_SYMBYTES)
+    key_a, tag_a, key_b, tag_b = handshake(pw, pw)
+    assert key_a == key_b
+    assert tag_a == tag_b
+
+
+def test_wrong_password():
+    with pytest.raises(chic.AuthenticationError):
+        handshake(os.urandom(chic.KYBER_SYMBYTES), os.urandom(chic.KYBER_SYMBYTES))
+
+
+def test_tampered_msg2():
+    pw = os.urandom(chic.KYBER_SYMBYTES)
+    msg, pk, sk = chic.init_start(pw)
+    sid, msg1 = msg[: chic.KYBER_SYMBYTES], msg[chic.KYBER_SYMBYTES :]
+    msg2, _, _ = chic.resp(sid, msg1, pw)
+    msg2 = bytes([msg2[0] ^ 1]) + msg2[1:]
+    with pytest.raises(chic.AuthenticationError):
+        chic.init_end(msg2, sid, msg1, pk, sk)
+
+
+def test_channel_binding_mismatch():
+    pw = os.urandom(chic.KYBER_SYMBYTES)
+    msg, pk, sk = chic.init_start(pw, channel_binding=b"channel a")
+    sid, msg1 = msg[: chic.KYBER_SYMBYTES], msg[chic.KYBER_SYMBYTES :]
+    msg2, key_b, _ = chic.resp(sid, msg1, pw, channel_binding=b"channel a")
+    key_a, _ = chic.init_end(msg2, sid, msg1, pk, sk, channel_binding=b"channel a")
+    assert key_a == key_b
+    msg2, _, _ = chic.resp(sid, msg1, pw, channel_binding=b"channel b")
+    with pytest.raises(chic.AuthenticationError):
+        chic.init_end(msg2, sid, msg1, pk, sk, channel_binding=b"channel a")
+
+
+def test_length_mismatch():
+    pw = os.urandom(chic.KYBER_SYMBYTES)
+    msg, _, _ = chic.init_start(pw)
+    sid, msg1 = msg[: chic.KYBER_SYMBYTES], msg[chic.KYBER_SYMBYTES :]
+    with pytest.raises(ValueError):
+        chic.resp(sid, msg1[:-1], pw)
+    with pytest.raises(ValueError):
+        chic.resp(sid, msg1, pw + b"\x00")
+
+
+def test_hic_round_trip():
+    pw = os.urandom(chic.KYBER_SYMBYTES)
+    sid = os.urandom(chic.KYBER_SYMBYTES)
+    msg, pk, _ = chic.init_start(pw)
+    icc = chic.hic_eval(pk, pw, sid)
+    assert chic.hic_inv(icc, pw, sid) == pk
@@ -1,102 +1,695 @@
-use rand_core::{CryptoRng,RngCore};
-use crate::{params::*,chic::*,error::*};
+use rand_core::{CryptoRng,RngCore,SeedableRng};
+use crate::{params::*,chic::*,error::*,limiter::GuessLimiter,credentials::CredentialStore,rng::{randombytes,ShakeRng},symmetric::hash_h};
+use crate::reference::{fips202::shake256,verify::verify};
+#[cfg(feature = "ml-kem")]
+use crate::mlkem;
 
-pub fn pake_init_start<R,F>(pw: &[u8;KYBER_SYMBYTES], rng: &mut R, mut keypair: F) -> PakeKeyPair
+/// Domain separation label for pake_derive_sid
+const SID_LABEL: &[u8;8] = b"CHIC-sid";
+/// Domain separation label for pake_export_key
+const EXPORT_LABEL: &[u8;11] = b"CHIC-export";
+/// Longest label accepted by pake_export_key
+pub const EXPORT_LABEL_MAX: usize = 64;
+
+/// Starts the initiator with a fresh random sid.
+///
+/// The first output is what goes on the wire: the sid (KYBER_SYMBYTES bytes) followed by msg1,
+/// the encrypted public key (MSG1_LEN bytes). The responder splits it with pake_split_msg1 and
+/// passes both halves to pake_resp.
+pub fn pake_init_start<R,F>(pw: &[u8;KYBER_SYMBYTES], rng: &mut R, keypair: F) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>
+{
+    pake_init_start_bound(pw, &[], rng, keypair)
+}
+
+/// pake_init_start bound to an outer channel.
+///
+/// channel_binding ties the session to an outer channel, e.g. a TLS exporter value. Both peers
+/// must use the _bound variant of every stage with the same bytes, otherwise key confirmation
+/// fails; an empty slice disables binding. It is not sent on the wire.
+pub fn pake_init_start_bound<R,F>(pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R, keypair: F) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>
+{
+    let mut sid = [0u8;KYBER_SSBYTES];
+    randombytes(&mut sid, KYBER_SSBYTES, rng)?;
+    pake_init_start_with_sid_bound(pw, &sid, channel_binding, rng, keypair)
+}
+
+/// Starts the initiator with a caller-provided sid, such as a channel identifier both sides
+/// already share, a pairing code or the output of pake_derive_sid.
+///
+/// The wire format is the same as for pake_init_start, the sid is still sent in front of msg1.
+/// A responder that knows the sid can pass its own copy to pake_resp instead of the received
+/// one; if the two differ, key confirmation fails.
+pub fn pake_init_start_with_sid<R,F>(pw: &[u8;KYBER_SYMBYTES], sid: &[u8;KYBER_SYMBYTES], rng: &mut R, keypair: F) -> PakeKeyPair
 where
     R: CryptoRng + RngCore,
-    F: FnMut(&mut R) -> ([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES])
+    F: FnMut(&mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>
+{
+    pake_init_start_with_sid_bound(pw, sid, &[], rng, keypair)
+}
+
+/// pake_init_start_with_sid bound to an outer channel, see pake_init_start_bound
+pub fn pake_init_start_with_sid_bound<R,F>(pw: &[u8;KYBER_SYMBYTES], sid: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R, keypair: F) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>
 {
     let mut out = [0u8; KYBER_PUBLICKEYBYTES];
     let mut pk = [0u8; KYBER_PUBLICKEYBYTES];
     let mut sk = [0u8;KYBER_SECRETKEYBYTES];
-    let mut sid = [0u8;KYBER_SSBYTES];
-    rng.fill_bytes(&mut sid);
-    init_start(&mut out, &mut pk, &mut sk, pw, &sid, rng, Some(keypair))?;
+    init_start(&mut out, &mut pk, &mut sk, pw, &bind_sid(sid, channel_binding), rng, Some(keypair))?;
     let mut enc_pk = [0u8;KYBER_SYMBYTES+KYBER_INDCPA_PUBLICKEYBYTES];
-    enc_pk[..KYBER_SYMBYTES].copy_from_slice(&sid);
+    enc_pk[..KYBER_SYMBYTES].copy_from_slice(sid);
     enc_pk[KYBER_SYMBYTES..].copy_from_slice(&out);
     Ok((enc_pk,pk,sk))
 }
 
+/// Derives a sid from one nonce contributed by each party, sid = H("CHIC-sid" || nonce_i || nonce_r).
+///
+/// The sid is an input of the half-ideal cipher, so both nonces have to be exchanged before the
+/// initiator computes msg1 (with pake_init_start_with_sid).
+pub fn pake_derive_sid(initiator_nonce: &[u8;KYBER_SYMBYTES], responder_nonce: &[u8;KYBER_SYMBYTES]) -> [u8;KYBER_SYMBYTES]
+{
+    let mut hashin = [0u8;SID_LABEL.len()+2*KYBER_SYMBYTES];
+    let mut sid = [0u8;KYBER_SYMBYTES];
+    hashin[..SID_LABEL.len()].copy_from_slice(SID_LABEL);
+    hashin[SID_LABEL.len()..SID_LABEL.len()+KYBER_SYMBYTES].copy_from_slice(initiator_nonce);
+    hashin[SID_LABEL.len()+KYBER_SYMBYTES..].copy_from_slice(responder_nonce);
+    hash_h(&mut sid, &hashin, hashin.len());
+    sid
+}
+
+/// Derives a key for an application protocol from the session key, as
+/// SHAKE256("CHIC-export" || key || len(label) || label). Different labels give independent keys,
+/// so the session key itself never has to be used outside the PAKE. Labels longer than
+/// EXPORT_LABEL_MAX are rejected with PakeError::ParameterMismatch.
+pub fn pake_export_key(key: &[u8;KYBER_SYMBYTES], label: &[u8]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
+{
+    if label.len() > EXPORT_LABEL_MAX {
+        return Err(PakeError::ParameterMismatch);
+    }
+    let mut hashin = [0u8;EXPORT_LABEL.len()+KYBER_SYMBYTES+1+EXPORT_LABEL_MAX];
+    let mut out = [0u8;KYBER_SYMBYTES];
+    let inlen = EXPORT_LABEL.len()+KYBER_SYMBYTES+1+label.len();
+    hashin[..EXPORT_LABEL.len()].copy_from_slice(EXPORT_LABEL);
+    hashin[EXPORT_LABEL.len()..EXPORT_LABEL.len()+KYBER_SYMBYTES].copy_from_slice(key);
+    hashin[EXPORT_LABEL.len()+KYBER_SYMBYTES] = label.len() as u8;
+    hashin[EXPORT_LABEL.len()+KYBER_SYMBYTES+1..inlen].copy_from_slice(label);
+    shake256(&mut out, KYBER_SYMBYTES, &hashin, inlen);
+    Ok(out)
+}
+
+/// Splits the initiator's wire message into the sid and msg1
+pub fn pake_split_msg1(enc_pk: &[u8;KYBER_SYMBYTES+MSG1_LEN]) -> ([u8;KYBER_SYMBYTES],[u8;MSG1_LEN])
+{
+    let mut sid = [0u8;KYBER_SYMBYTES];
+    let mut msg1 = [0u8;MSG1_LEN];
+    sid.copy_from_slice(&enc_pk[..KYBER_SYMBYTES]);
+    msg1.copy_from_slice(&enc_pk[KYBER_SYMBYTES..]);
+    (sid, msg1)
+}
 
 pub fn pake_resp<R,F>(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], rng: &mut R, encapsulate: F) -> PakeEncapsulated
 where
     R: CryptoRng + RngCore,
-    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> ([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES])
+    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>
+{
+    pake_resp_bound(sid, pk, pw, &[], rng, encapsulate)
+}
+
+/// pake_resp bound to an outer channel, see pake_init_start_bound
+pub fn pake_resp_bound<R,F>(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R, encapsulate: F) -> PakeEncapsulated
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>
 {
     if pk.len() != KYBER_PUBLICKEYBYTES {
-        return Err(PakeError::InvalidInput);
+        return Err(PakeError::MalformedMessage);
     }
     let mut ct = [0u8; KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES];
     let mut ss = [0u8; KYBER_SYMBYTES];
     let mut init_tag = [0u8; KYBER_SYMBYTES];
-    resp(&mut ss, &mut ct, &mut init_tag, pk, pw, &sid, rng, Some(encapsulate))?;
+    resp(&mut ss, &mut ct, &mut init_tag, pk, pw, &bind_sid(sid, channel_binding), rng, Some(encapsulate))?;
     Ok((ct, ss, init_tag))
 }
 
+/// Like pake_resp, but only runs if the guess limiter allows another attempt for account.
+/// Every call lets the initiator test one password guess, so a responder facing untrusted
+/// initiators should use this and report the outcome with pake_resp_confirm_limited.
+pub fn pake_resp_limited<L,R,F>(limiter: &mut L, account: &[u8], sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R, encapsulate: F) -> PakeEncapsulated
+where
+    L: GuessLimiter,
+    R: CryptoRng + RngCore,
+    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>
+{
+    limiter.check(account)?;
+    pake_resp_bound(sid, pk, pw, channel_binding, rng, encapsulate)
+}
+
+/// Like pake_resp, with pw taken from the credential record of username.
+///
+/// If the account is not registered, or its record belongs to another parameter set, the
+/// responder runs with a random pw instead, so the initiator only learns at key confirmation
+/// that the exchange failed, exactly as for a wrong password, and cannot tell which accounts
+/// exist.
+pub fn pake_resp_stored<S,R,F>(store: &S, username: &[u8], sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], channel_binding: &[u8], rng: &mut R, encapsulate: F) -> PakeEncapsulated
+where
+    S: CredentialStore,
+    R: CryptoRng + RngCore,
+    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>
+{
+    match store.lookup(username)? {
+        Some(record) if record.check_param_set().is_ok() => {
+            pake_resp_bound(sid, pk, &record.pw, channel_binding, rng, encapsulate)
+        }
+        _ => {
+            let mut fake_pw = [0u8;KYBER_SYMBYTES];
+            randombytes(&mut fake_pw, KYBER_SYMBYTES, rng)?;
+            pake_resp_bound(sid, pk, &fake_pw, channel_binding, rng, encapsulate)
+        }
+    }
+}
+
+/// Responder side of key confirmation: compares, in constant time, the init_tag returned by
+/// pake_resp with the tag received from the initiator.
+pub fn pake_resp_confirm(expected_tag: &[u8;KYBER_SYMBYTES], received_tag: &[u8;KYBER_SYMBYTES]) -> Result<(), PakeError>
+{
+    if verify(expected_tag, received_tag, KYBER_SYMBYTES) != 0 {
+        return Err(PakeError::AuthenticationFailure);
+    }
+    Ok(())
+}
+
+/// Like pake_resp_confirm, and reports the outcome to the guess limiter
+pub fn pake_resp_confirm_limited<L>(limiter: &mut L, account: &[u8], expected_tag: &[u8;KYBER_SYMBYTES], received_tag: &[u8;KYBER_SYMBYTES]) -> Result<(), PakeError>
+where
+    L: GuessLimiter
+{
+    let result = pake_resp_confirm(expected_tag, received_tag);
+    match result {
+        Ok(()) => limiter.record_success(account),
+        Err(_) => limiter.record_failure(account),
+    }
+    result
+}
+
 pub fn pake_init_end<F>(ct: &[u8;KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES], sid: &[u8; KYBER_SYMBYTES], enc_pk: &[u8;KYBER_PUBLICKEYBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], sk: &[u8;KYBER_SECRETKEYBYTES], decapsulate: F) -> PakeDecapsulated
 where
-    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> [u8;KYBER_SYMBYTES]
+    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
+{
+    pake_init_end_bound(ct, sid, enc_pk, pk, sk, &[], decapsulate)
+}
+
+/// pake_init_end bound to an outer channel, see pake_init_start_bound
+pub fn pake_init_end_bound<F>(ct: &[u8;KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES], sid: &[u8; KYBER_SYMBYTES], enc_pk: &[u8;KYBER_PUBLICKEYBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], sk: &[u8;KYBER_SECRETKEYBYTES], channel_binding: &[u8], decapsulate: F) -> PakeDecapsulated
+where
+    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
 {
     if pk.len() != KYBER_PUBLICKEYBYTES {
-        return Err(PakeError::InvalidInput);
+        return Err(PakeError::MalformedMessage);
     }
     let mut ss = [0u8; KYBER_SYMBYTES];
     let mut init_tag = [0u8; KYBER_SYMBYTES];
-    let result = init_end(&mut ss, &mut init_tag,  ct, enc_pk, pk, sk, &sid, Some(decapsulate))?;
-    Ok((ss,init_tag,result))
+    init_end(&mut ss, &mut init_tag,  ct, enc_pk, pk, sk, &bind_sid(sid, channel_binding), Some(decapsulate))?;
+    Ok((ss,init_tag))
+}
+
+/// Like pake_init_end, but with implicit rejection: if the responder's tag does not verify, the
+/// returned key is derived from rejection_seed and the transcript instead of an error being
+/// returned. The responder learns about the failure through key confirmation. rejection_seed
+/// must be secret and fresh for every session.
+pub fn pake_init_end_implicit<F>(ct: &[u8;KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES], sid: &[u8; KYBER_SYMBYTES], enc_pk: &[u8;KYBER_PUBLICKEYBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], sk: &[u8;KYBER_SECRETKEYBYTES], rejection_seed: &[u8;KYBER_SYMBYTES], decapsulate: F) -> PakeDecapsulated
+where
+    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
+{
+    pake_init_end_implicit_bound(ct, sid, enc_pk, pk, sk, &[], rejection_seed, decapsulate)
+}
+
+/// pake_init_end_implicit bound to an outer channel, see pake_init_start_bound
+pub fn pake_init_end_implicit_bound<F>(ct: &[u8;KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES], sid: &[u8; KYBER_SYMBYTES], enc_pk: &[u8;KYBER_PUBLICKEYBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], sk: &[u8;KYBER_SECRETKEYBYTES], channel_binding: &[u8], rejection_seed: &[u8;KYBER_SYMBYTES], decapsulate: F) -> PakeDecapsulated
+where
+    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
+{
+    let mut ss = [0u8; KYBER_SYMBYTES];
+    let mut init_tag = [0u8; KYBER_SYMBYTES];
+    init_end_implicit(&mut ss, &mut init_tag, ct, enc_pk, pk, sk, &bind_sid(sid, channel_binding), rejection_seed, Some(decapsulate))?;
+    Ok((ss,init_tag))
+}
+
+/// Deterministic pake_init_start for tests, known answer tests and replaying logged transcripts.
+///
+/// The sid is the first KYBER_SYMBYTES bytes of ShakeRng(sid_seed) and keypair draws from
+/// ShakeRng(keygen_seed), so the same seeds always give the same msg1 and keys. Never reuse seeds
+/// outside of testing.
+pub fn pake_init_start_seeded<F>(pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], sid_seed: &[u8;KYBER_SYMBYTES], keygen_seed: &[u8;KYBER_SYMBYTES], keypair: F) -> PakeKeyPair
+where
+    F: FnMut(&mut ShakeRng) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>
+{
+    let mut sid = [0u8;KYBER_SYMBYTES];
+    ShakeRng::from_seed(*sid_seed).fill_bytes(&mut sid);
+    pake_init_start_with_sid_bound(pw, &sid, channel_binding, &mut ShakeRng::from_seed(*keygen_seed), keypair)
+}
+
+/// Deterministic pake_resp, encapsulate draws from ShakeRng(encaps_seed). See pake_init_start_seeded.
+pub fn pake_resp_seeded<F>(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], encaps_seed: &[u8;KYBER_SYMBYTES], encapsulate: F) -> PakeEncapsulated
+where
+    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut ShakeRng) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>
+{
+    pake_resp_bound(sid, pk, pw, channel_binding, &mut ShakeRng::from_seed(*encaps_seed), encapsulate)
 }
 
 #[cfg(feature = "default-kyber")]
 pub fn pake_init_start_ref<R>(pw: &[u8;KYBER_SYMBYTES], rng: &mut R) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore
+{
+    pake_init_start_ref_bound(pw, &[], rng)
+}
+
+#[cfg(feature = "default-kyber")]
+pub fn pake_init_start_ref_bound<R>(pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore
+{
+    let mut sid = [0u8;KYBER_SSBYTES];
+    randombytes(&mut sid, KYBER_SSBYTES, rng)?;
+    pake_init_start_ref_with_sid_bound(pw, &sid, channel_binding, rng)
+}
+
+#[cfg(feature = "default-kyber")]
+pub fn pake_init_start_ref_with_sid<R>(pw: &[u8;KYBER_SYMBYTES], sid: &[u8;KYBER_SYMBYTES], rng: &mut R) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore
+{
+    pake_init_start_ref_with_sid_bound(pw, sid, &[], rng)
+}
+
+#[cfg(feature = "default-kyber")]
+pub fn pake_init_start_ref_with_sid_bound<R>(pw: &[u8;KYBER_SYMBYTES], sid: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R) -> PakeKeyPair
 where
     R: CryptoRng + RngCore
 {
     let mut out = [0u8; KYBER_PUBLICKEYBYTES];
     let mut pk = [0u8; KYBER_PUBLICKEYBYTES];
     let mut sk = [0u8;KYBER_SECRETKEYBYTES];
-    let mut sid = [0u8;KYBER_SSBYTES];
-    rng.fill_bytes(&mut sid);
 
-    let keypair_none: Option<fn(&mut R) -> ([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES])> = None;
+    let keypair_none: Option<fn(&mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>> = None;
 
-    init_start(&mut out, &mut pk, &mut sk, pw, &sid, rng, keypair_none)?;
+    init_start(&mut out, &mut pk, &mut sk, pw, &bind_sid(sid, channel_binding), rng, keypair_none)?;
     let mut enc_pk = [0u8;KYBER_SYMBYTES+KYBER_INDCPA_PUBLICKEYBYTES];
-    enc_pk[..KYBER_SYMBYTES].copy_from_slice(&sid);
+    enc_pk[..KYBER_SYMBYTES].copy_from_slice(sid);
     enc_pk[KYBER_SYMBYTES..].copy_from_slice(&out);
     Ok((enc_pk,pk,sk))
 }
 
 #[cfg(feature = "default-kyber")]
 pub fn pake_resp_ref<R>(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], rng: &mut R) -> PakeEncapsulated
+where
+    R: CryptoRng + RngCore
+{
+    pake_resp_ref_bound(sid, pk, pw, &[], rng)
+}
+
+#[cfg(feature = "default-kyber")]
+pub fn pake_resp_ref_bound<R>(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R) -> PakeEncapsulated
 where
     R: CryptoRng + RngCore,
 {
     if pk.len() != KYBER_PUBLICKEYBYTES {
-        return Err(PakeError::InvalidInput);
+        return Err(PakeError::MalformedMessage);
     }
     let mut ct = [0u8; KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES];
     let mut ss = [0u8; KYBER_SYMBYTES];
 
-    let encapsulate_none: Option<fn(&[u8;KYBER_PUBLICKEYBYTES], &mut R) -> ([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES])> = None;
+    let encapsulate_none: Option<fn(&[u8;KYBER_PUBLICKEYBYTES], &mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>> = None;
 
-    resp(&mut ss, &mut ct, pk, pw, &sid, rng, encapsulate_none)?;
-    Ok((ct, ss))
+    let mut init_tag = [0u8; KYBER_SYMBYTES];
+
+    resp(&mut ss, &mut ct, &mut init_tag, pk, pw, &bind_sid(sid, channel_binding), rng, encapsulate_none)?;
+    Ok((ct, ss, init_tag))
 }
 
 #[cfg(feature = "default-kyber")]
 pub fn pake_init_end_ref(ct: &[u8;KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES], sid: &[u8; KYBER_SYMBYTES], enc_pk: &[u8;KYBER_PUBLICKEYBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], sk: &[u8;KYBER_SECRETKEYBYTES]) -> PakeDecapsulated
+{
+    pake_init_end_ref_bound(ct, sid, enc_pk, pk, sk, &[])
+}
+
+#[cfg(feature = "default-kyber")]
+pub fn pake_init_end_ref_bound(ct: &[u8;KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES], sid: &[u8; KYBER_SYMBYTES], enc_pk: &[u8;KYBER_PUBLICKEYBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], sk: &[u8;KYBER_SECRETKEYBYTES], channel_binding: &[u8]) -> PakeDecapsulated
 {
     
     if pk.len() != KYBER_PUBLICKEYBYTES {
-        return Err(PakeError::InvalidInput);
+        return Err(PakeError::MalformedMessage);
     }
-    let mut ct_and_tag = [0u8; KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES];
-    ct_and_tag.copy_from_slice(ct);
     let mut ss = [0u8; KYBER_SYMBYTES];
+    let mut init_tag = [0u8; KYBER_SYMBYTES];
+
+    let decapsulate_none: Option<fn(&[u8;KYBER_CIPHERTEXTBYTES], &[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>> = None;
+
+    init_end(&mut ss, &mut init_tag, ct, enc_pk, pk, sk, &bind_sid(sid, channel_binding), decapsulate_none)?;
+    Ok((ss,init_tag))
+}
+
+/// pake_init_start_seeded with the default Kyber implementation
+#[cfg(feature = "default-kyber")]
+pub fn pake_init_start_ref_seeded(pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], sid_seed: &[u8;KYBER_SYMBYTES], keygen_seed: &[u8;KYBER_SYMBYTES]) -> PakeKeyPair
+{
+    let mut sid = [0u8;KYBER_SYMBYTES];
+    ShakeRng::from_seed(*sid_seed).fill_bytes(&mut sid);
+    pake_init_start_ref_with_sid_bound(pw, &sid, channel_binding, &mut ShakeRng::from_seed(*keygen_seed))
+}
 
-    let decapsulate_none: Option<fn(&[u8;KYBER_CIPHERTEXTBYTES], &[u8;KYBER_SECRETKEYBYTES]) -> [u8;KYBER_SYMBYTES]> = None;
+/// pake_resp_seeded with the default Kyber implementation
+#[cfg(feature = "default-kyber")]
+pub fn pake_resp_ref_seeded(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], encaps_seed: &[u8;KYBER_SYMBYTES]) -> PakeEncapsulated
+{
+    pake_resp_ref_bound(sid, pk, pw, channel_binding, &mut ShakeRng::from_seed(*encaps_seed))
+}
 
-    let result = init_end(&mut ss, &mut ct_and_tag, enc_pk, pk, sk, &sid, decapsulate_none)?;
-    Ok((ss,result))
-}
\ No newline at end of file
+#[cfg(feature = "ml-kem")]
+pub fn pake_init_start_mlkem<R>(pw: &[u8;KYBER_SYMBYTES], rng: &mut R) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore
+{
+    pake_init_start_mlkem_bound(pw, &[], rng)
+}
+
+#[cfg(feature = "ml-kem")]
+pub fn pake_init_start_mlkem_bound<R>(pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore
+{
+    let mut sid = [0u8;KYBER_SSBYTES];
+    randombytes(&mut sid, KYBER_SSBYTES, rng)?;
+    pake_init_start_mlkem_with_sid_bound(pw, &sid, channel_binding, rng)
+}
+
+#[cfg(feature = "ml-kem")]
+pub fn pake_init_start_mlkem_with_sid<R>(pw: &[u8;KYBER_SYMBYTES], sid: &[u8;KYBER_SYMBYTES], rng: &mut R) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore
+{
+    pake_init_start_mlkem_with_sid_bound(pw, sid, &[], rng)
+}
+
+#[cfg(feature = "ml-kem")]
+pub fn pake_init_start_mlkem_with_sid_bound<R>(pw: &[u8;KYBER_SYMBYTES], sid: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore
+{
+    let mut coins = [0u8;mlkem::MLKEM_KEYGEN_SEEDBYTES];
+    randombytes(&mut coins, mlkem::MLKEM_KEYGEN_SEEDBYTES, rng)?;
+    pake_init_start_with_sid_bound(pw, sid, channel_binding, rng, |_: &mut R| Ok(mlkem::keypair_derand(&coins)))
+}
+
+#[cfg(feature = "ml-kem")]
+pub fn pake_resp_mlkem<R>(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], rng: &mut R) -> PakeEncapsulated
+where
+    R: CryptoRng + RngCore
+{
+    pake_resp_mlkem_bound(sid, pk, pw, &[], rng)
+}
+
+#[cfg(feature = "ml-kem")]
+pub fn pake_resp_mlkem_bound<R>(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R) -> PakeEncapsulated
+where
+    R: CryptoRng + RngCore
+{
+    let mut coins = [0u8;mlkem::MLKEM_ENCAPS_SEEDBYTES];
+    randombytes(&mut coins, mlkem::MLKEM_ENCAPS_SEEDBYTES, rng)?;
+    pake_resp_bound(sid, pk, pw, channel_binding, rng, |pk: &[u8;KYBER_PUBLICKEYBYTES], _: &mut R| Ok(mlkem::encapsulate_derand(pk, &coins)))
+}
+
+#[cfg(feature = "ml-kem")]
+pub fn pake_init_end_mlkem(ct: &[u8;KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES], sid: &[u8; KYBER_SYMBYTES], enc_pk: &[u8;KYBER_PUBLICKEYBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], sk: &[u8;KYBER_SECRETKEYBYTES]) -> PakeDecapsulated
+{
+    pake_init_end_mlkem_bound(ct, sid, enc_pk, pk, sk, &[])
+}
+
+#[cfg(feature = "ml-kem")]
+pub fn pake_init_end_mlkem_bound(ct: &[u8;KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES], sid: &[u8; KYBER_SYMBYTES], enc_pk: &[u8;KYBER_PUBLICKEYBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], sk: &[u8;KYBER_SECRETKEYBYTES], channel_binding: &[u8]) -> PakeDecapsulated
+{
+    pake_init_end_bound(ct, sid, enc_pk, pk, sk, channel_binding, |ct: &[u8;KYBER_CIPHERTEXTBYTES], sk: &[u8;KYBER_SECRETKEYBYTES]| Ok(mlkem::decapsulate(ct, sk)))
+}
+
+/// pake_init_start_seeded with ML-KEM
+#[cfg(feature = "ml-kem")]
+pub fn pake_init_start_mlkem_seeded(pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], sid_seed: &[u8;KYBER_SYMBYTES], keygen_seed: &[u8;KYBER_SYMBYTES]) -> PakeKeyPair
+{
+    let mut sid = [0u8;KYBER_SYMBYTES];
+    ShakeRng::from_seed(*sid_seed).fill_bytes(&mut sid);
+    pake_init_start_mlkem_with_sid_bound(pw, &sid, channel_binding, &mut ShakeRng::from_seed(*keygen_seed))
+}
+
+/// pake_resp_seeded with ML-KEM
+#[cfg(feature = "ml-kem")]
+pub fn pake_resp_mlkem_seeded(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], encaps_seed: &[u8;KYBER_SYMBYTES]) -> PakeEncapsulated
+{
+    pake_resp_mlkem_bound(sid, pk, pw, channel_binding, &mut ShakeRng::from_seed(*encaps_seed))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use core::num::NonZeroU32;
+    use crate::testing::*;
+    use super::*;
+
+    /// RNG whose fallible interface always fails, like a faulty hardware RNG
+    struct FailingRng;
+
+    impl RngCore for FailingRng {
+        fn next_u32(&mut self) -> u32 { 0 }
+        fn next_u64(&mut self) -> u64 { 0 }
+        fn fill_bytes(&mut self, dest: &mut [u8]) { dest.fill(0) }
+        fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            Err(rand_core::Error::from(NonZeroU32::new(rand_core::Error::CUSTOM_START).unwrap()))
+        }
+    }
+
+    impl CryptoRng for FailingRng {}
+
+    #[test]
+    fn test_rng_failure_is_reported() {
+        let pw = [0u8;KYBER_SYMBYTES];
+        assert_eq!(pake_init_start(&pw, &mut FailingRng, keypair_func), Err(PakeError::RandomBytesGeneration));
+    }
+
+    #[test]
+    fn test_kem_failure_is_reported() {
+        let mut rng = rand::thread_rng();
+        let pw = [0u8;KYBER_SYMBYTES];
+        let keypair_func = |_: &mut rand::rngs::ThreadRng| Err(PakeError::Kem(KyberError::RandomBytesGeneration));
+        assert_eq!(pake_init_start(&pw, &mut rng, keypair_func), Err(PakeError::Kem(KyberError::RandomBytesGeneration)));
+
+        let encapsulate_func = |_: &[u8;KYBER_PUBLICKEYBYTES], _: &mut rand::rngs::ThreadRng| Err(PakeError::Kem(KyberError::InvalidInput));
+        let decapsulate_func = |_: &[u8;KYBER_CIPHERTEXTBYTES], _: &[u8;KYBER_SECRETKEYBYTES]| Err(PakeError::Kem(KyberError::Decapsulation));
+        let (enc_pk, pk, sk) = pake_init_start(&pw, &mut rng, crate::testing::keypair_func).unwrap();
+        let (sid, msg1) = pake_split_msg1(&enc_pk);
+        assert_eq!(pake_resp(&sid, &msg1, &pw, &mut rng, encapsulate_func), Err(PakeError::Kem(KyberError::InvalidInput)));
+        assert_eq!(pake_init_end(&[0u8;MSG2_LEN], &sid, &msg1, &pk, &sk, decapsulate_func), Err(PakeError::Kem(KyberError::Decapsulation)));
+    }
+
+    #[test]
+    fn test_wrong_password_yields_no_key() {
+        let mut rng = rand::thread_rng();
+        let mut pw_a = [0u8;KYBER_SYMBYTES];
+        let mut pw_b = [0u8;KYBER_SYMBYTES];
+        rng.fill_bytes(&mut pw_a);
+        rng.fill_bytes(&mut pw_b);
+
+        let (enc_pk, pk, sk) = pake_init_start(&pw_a, &mut rng, keypair_func).unwrap();
+        let mut sid = [0u8;KYBER_SYMBYTES];
+        let mut msg1 = [0u8;MSG1_LEN];
+        sid.copy_from_slice(&enc_pk[..KYBER_SYMBYTES]);
+        msg1.copy_from_slice(&enc_pk[KYBER_SYMBYTES..]);
+
+        let (msg2, _, _) = pake_resp(&sid, &msg1, &pw_b, &mut rng, encapsulate_func).unwrap();
+        assert_eq!(pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func), Err(PakeError::AuthenticationFailure));
+    }
+
+    #[test]
+    fn test_derive_sid() {
+        let n_i = [1u8;KYBER_SYMBYTES];
+        let n_r = [2u8;KYBER_SYMBYTES];
+        assert_eq!(pake_derive_sid(&n_i, &n_r), pake_derive_sid(&n_i, &n_r));
+        assert_ne!(pake_derive_sid(&n_i, &n_r), pake_derive_sid(&n_r, &n_i));
+    }
+
+    #[test]
+    fn test_negotiated_sid() {
+        let mut rng = rand::thread_rng();
+        let mut pw = [0u8;KYBER_SYMBYTES];
+        let mut n_i = [0u8;KYBER_SYMBYTES];
+        let mut n_r = [0u8;KYBER_SYMBYTES];
+        rng.fill_bytes(&mut pw);
+        rng.fill_bytes(&mut n_i);
+        rng.fill_bytes(&mut n_r);
+
+        let sid_a = pake_derive_sid(&n_i, &n_r);
+        let (enc_pk, pk, sk) = pake_init_start_with_sid(&pw, &sid_a, &mut rng, keypair_func).unwrap();
+        let (sid, msg1) = pake_split_msg1(&enc_pk);
+        assert_eq!(sid, sid_a);
+
+        // The responder uses its own copy of the sid
+        let sid_b = pake_derive_sid(&n_i, &n_r);
+        let (msg2, key_b, tag_b) = pake_resp(&sid_b, &msg1, &pw, &mut rng, encapsulate_func).unwrap();
+        let (key_a, tag_a) = pake_init_end(&msg2, &sid_a, &msg1, &pk, &sk, decapsulate_func).unwrap();
+        assert_eq!(key_a, key_b);
+        assert_eq!(tag_a, tag_b);
+
+        // A responder that derived a different sid fails key confirmation
+        let sid_c = pake_derive_sid(&n_r, &n_i);
+        let (msg2, _, _) = pake_resp(&sid_c, &msg1, &pw, &mut rng, encapsulate_func).unwrap();
+        assert_eq!(pake_init_end(&msg2, &sid_a, &msg1, &pk, &sk, decapsulate_func), Err(PakeError::AuthenticationFailure));
+    }
+
+    #[test]
+    fn test_channel_binding() {
+        let mut rng = rand::thread_rng();
+        let mut pw = [0u8;KYBER_SYMBYTES];
+        rng.fill_bytes(&mut pw);
+        let cb_a = b"tls-exporter of the initiator's channel";
+        let cb_b = b"tls-exporter of the responder's channel";
+
+        // Same binding on both sides
+        let (enc_pk, pk, sk) = pake_init_start_bound(&pw, cb_a, &mut rng, keypair_func).unwrap();
+        let (sid, msg1) = pake_split_msg1(&enc_pk);
+        let (msg2, key_b, tag_b) = pake_resp_bound(&sid, &msg1, &pw, cb_a, &mut rng, encapsulate_func).unwrap();
+        let (key_a, tag_a) = pake_init_end_bound(&msg2, &sid, &msg1, &pk, &sk, cb_a, decapsulate_func).unwrap();
+        assert_eq!(key_a, key_b);
+        assert_eq!(tag_a, tag_b);
+
+        // A man in the middle terminates two different outer channels
+        let (msg2, _, _) = pake_resp_bound(&sid, &msg1, &pw, cb_b, &mut rng, encapsulate_func).unwrap();
+        assert_eq!(pake_init_end_bound(&msg2, &sid, &msg1, &pk, &sk, cb_a, decapsulate_func), Err(PakeError::AuthenticationFailure));
+
+        // Binding on one side only
+        let (msg2, _, _) = pake_resp(&sid, &msg1, &pw, &mut rng, encapsulate_func).unwrap();
+        assert_eq!(pake_init_end_bound(&msg2, &sid, &msg1, &pk, &sk, cb_a, decapsulate_func), Err(PakeError::AuthenticationFailure));
+    }
+
+    /// Allows a fixed number of attempts and records the reported outcomes
+    #[derive(Default)]
+    struct CountingLimiter {
+        left: u32,
+        failures: u32,
+        successes: u32,
+    }
+
+    impl GuessLimiter for CountingLimiter {
+        fn check(&mut self, _account: &[u8]) -> Result<(), PakeError> {
+            if self.left == 0 {
+                return Err(PakeError::RateLimited);
+            }
+            self.left -= 1;
+            Ok(())
+        }
+        fn record_failure(&mut self, _account: &[u8]) { self.failures += 1 }
+        fn record_success(&mut self, _account: &[u8]) { self.successes += 1 }
+    }
+
+    #[test]
+    fn test_resp_limited() {
+        let mut rng = rand::thread_rng();
+        let mut pw = [0u8;KYBER_SYMBYTES];
+        let mut wrong_pw = [0u8;KYBER_SYMBYTES];
+        rng.fill_bytes(&mut pw);
+        rng.fill_bytes(&mut wrong_pw);
+        let mut limiter = CountingLimiter { left: 2, ..Default::default() };
+
+        // Honest run
+        let (enc_pk, pk, sk) = pake_init_start(&pw, &mut rng, keypair_func).unwrap();
+        let (sid, msg1) = pake_split_msg1(&enc_pk);
+        let (msg2, _, expected_tag) = pake_resp_limited(&mut limiter, b"alice", &sid, &msg1, &pw, &[], &mut rng, encapsulate_func).unwrap();
+        let (_, tag) = pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func).unwrap();
+        assert_eq!(pake_resp_confirm_limited(&mut limiter, b"alice", &expected_tag, &tag), Ok(()));
+
+        // A guess with the wrong password; the attacker returns some tag
+        let (enc_pk, _, _) = pake_init_start(&wrong_pw, &mut rng, keypair_func).unwrap();
+        let (sid, msg1) = pake_split_msg1(&enc_pk);
+        let (_, _, expected_tag) = pake_resp_limited(&mut limiter, b"alice", &sid, &msg1, &pw, &[], &mut rng, encapsulate_func).unwrap();
+        assert_eq!(pake_resp_confirm_limited(&mut limiter, b"alice", &expected_tag, &[0u8;KYBER_SYMBYTES]), Err(PakeError::AuthenticationFailure));
+        assert_eq!((limiter.successes, limiter.failures), (1, 1));
+
+        // Out of attempts
+        assert_eq!(pake_resp_limited(&mut limiter, b"alice", &sid, &msg1, &pw, &[], &mut rng, encapsulate_func), Err(PakeError::RateLimited));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_resp_stored() {
+        use crate::credentials::*;
+
+        let mut rng = rand::thread_rng();
+        let mut store = MemoryCredentialStore::new();
+        let params = HashParams { m_cost: 64, t_cost: 1, p_cost: 1 };
+        let record = CredentialRecord::new(&[5u8;KYBER_SYMBYTES], PasswordHashAlgorithm::Raw, &[0u8;SALT_BYTES], params).unwrap();
+        store.register(b"alice", record.clone()).unwrap();
+
+        let pw = derive_pw(&[5u8;KYBER_SYMBYTES], record.algorithm, &record.salt, &record.params).unwrap();
+        let (enc_pk, pk, sk) = pake_init_start(&pw, &mut rng, keypair_func).unwrap();
+        let (sid, msg1) = pake_split_msg1(&enc_pk);
+        let (msg2, key_b, _) = pake_resp_stored(&store, b"alice", &sid, &msg1, &[], &mut rng, encapsulate_func).unwrap();
+        let (key_a, _) = pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func).unwrap();
+        assert_eq!(key_a, key_b);
+
+        // An unknown account runs to completion and only fails at key confirmation
+        let (msg2, _, _) = pake_resp_stored(&store, b"bob", &sid, &msg1, &[], &mut rng, encapsulate_func).unwrap();
+        assert_eq!(pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func), Err(PakeError::AuthenticationFailure));
+        let mut other_set = record.clone();
+        other_set.param_set ^= 1;
+        store.change_password(b"alice", other_set).unwrap();
+        let (msg2, _, _) = pake_resp_stored(&store, b"alice", &sid, &msg1, &[], &mut rng, encapsulate_func).unwrap();
+        assert_eq!(pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func), Err(PakeError::AuthenticationFailure));
+    }
+
+    #[test]
+    fn test_export_key() {
+        let key = [1u8;KYBER_SYMBYTES];
+        let a = pake_export_key(&key, b"a").unwrap();
+        assert_eq!(pake_export_key(&key, b"a"), Ok(a));
+        assert_ne!(pake_export_key(&key, b"b"), Ok(a));
+        assert_ne!(pake_export_key(&[2u8;KYBER_SYMBYTES], b"a"), Ok(a));
+        assert_ne!(a, key);
+        assert_eq!(pake_export_key(&key, &[0u8;EXPORT_LABEL_MAX+1]), Err(PakeError::ParameterMismatch));
+    }
+
+    #[test]
+    fn test_empty_channel_binding_keeps_sid() {
+        let sid = [7u8;KYBER_SYMBYTES];
+        assert_eq!(bind_sid(&sid, &[]), sid);
+        assert_ne!(bind_sid(&sid, b"cb"), sid);
+        assert_ne!(bind_sid(&sid, b"cb"), bind_sid(&sid, b"cc"));
+    }
+
+    #[test]
+    fn test_seeded_transcript_replays() {
+        let pw = [3u8;KYBER_SYMBYTES];
+
+        let run = |keygen_seed: &[u8;KYBER_SYMBYTES]| {
+            let (enc_pk, pk, sk) = pake_init_start_seeded(&pw, &[], &[1u8;KYBER_SYMBYTES], keygen_seed, keypair_func).unwrap();
+            let (sid, msg1) = pake_split_msg1(&enc_pk);
+            let (msg2, key_b, _) = pake_resp_seeded(&sid, &msg1, &pw, &[], &[2u8;KYBER_SYMBYTES], encapsulate_func).unwrap();
+            let (key_a, _) = pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func).unwrap();
+            assert_eq!(key_a, key_b);
+            (enc_pk, msg2, key_a)
+        };
+
+        // The same seeds give the same transcript and key
+        let (enc_pk, msg2, key) = run(&[0u8;KYBER_SYMBYTES]);
+        assert_eq!(run(&[0u8;KYBER_SYMBYTES]), (enc_pk, msg2, key));
+
+        // Only the keygen seed differs: same sid, different msg1 and key
+        let (enc_pk_2, _, key_2) = run(&[9u8;KYBER_SYMBYTES]);
+        assert_eq!(enc_pk_2[..KYBER_SYMBYTES], enc_pk[..KYBER_SYMBYTES]);
+        assert_ne!(enc_pk_2[KYBER_SYMBYTES..], enc_pk[KYBER_SYMBYTES..]);
+        assert_ne!(key_2, key);
+    }
+}
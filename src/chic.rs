@@ -1,10 +1,39 @@
-use crate::hic::hic_inv;
-use crate::symmetric::{hash_g,hash_h};
+use crate::hic::{hic_eval_in,hic_inv_in};
+use crate::symmetric::{hash_h,HashG,HashH,Kdf};
+use crate::workspace::PakeWorkspace;
 use crate::reference::verify::{cmov,verify};
 use crate::params::{KYBER_CIPHERTEXTBYTES, KYBER_PUBLICKEYBYTES, KYBER_SECRETKEYBYTES, KYBER_SYMBYTES, MSG1_LEN, MSG2_LEN};
 use rand_core::{CryptoRng, RngCore};
 use crate::{hic,error::*};
 
+/// Domain separation label for bind_sid
+const CHANNEL_BINDING_LABEL: &[u8;7] = b"CHIC-cb";
+
+/// Name:  bind_sid
+///
+/// Description: Binds the sid to an outer channel (e.g. a TLS exporter value). The bound sid,
+///  H("CHIC-cb" || sid || H(channel_binding)), replaces the sid as input of the HIC and of the
+///  transcript, so peers on different channels fail key confirmation. An empty channel binding
+///  leaves the sid unchanged.
+///
+/// Arguments:   - [u8] sid: the input sid (of length KYBER_SYMBYTES)
+///              - [u8] channel_binding: the channel binding (of any length)
+///
+/// Returns the sid to be used by the protocol
+pub fn bind_sid(sid: &[u8;KYBER_SYMBYTES], channel_binding: &[u8]) -> [u8;KYBER_SYMBYTES] {
+    if channel_binding.is_empty() {
+        return *sid;
+    }
+    let mut cb_hash = [0u8;KYBER_SYMBYTES];
+    let mut hashin = [0u8;CHANNEL_BINDING_LABEL.len()+2*KYBER_SYMBYTES];
+    let mut bound = [0u8;KYBER_SYMBYTES];
+    hash_h(&mut cb_hash, channel_binding, channel_binding.len());
+    hashin[..CHANNEL_BINDING_LABEL.len()].copy_from_slice(CHANNEL_BINDING_LABEL);
+    hashin[CHANNEL_BINDING_LABEL.len()..CHANNEL_BINDING_LABEL.len()+KYBER_SYMBYTES].copy_from_slice(sid);
+    hashin[CHANNEL_BINDING_LABEL.len()+KYBER_SYMBYTES..].copy_from_slice(&cb_hash);
+    hash_h(&mut bound, &hashin, hashin.len());
+    bound
+}
 
 /// Name:  init_start
 ///
@@ -29,30 +58,63 @@ pub fn init_start<R,F>(
 ) -> Result<(), PakeError>
 where
     R: RngCore + CryptoRng,
-    F: FnMut(&mut R) -> ([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES])
+    F: FnMut(&mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>
 {
     match maybe_keypair {
         Some(mut keypair) => {
-            let (kyber_pk,kyber_sk) = keypair(_rng);
+            let (kyber_pk,kyber_sk) = keypair(_rng)?;
             pk.copy_from_slice(&kyber_pk);
             sk.copy_from_slice(&kyber_sk);
         }
         None => {
             #[cfg(feature = "default-kyber")] {
-                let keys = pqc_kyber::keypair(_rng).unwrap();
+                let keys = pqc_kyber::keypair(_rng)?;
                 pk.copy_from_slice(&keys.public);
                 sk.copy_from_slice(&keys.secret);
             }
             #[cfg(not(feature = "default-kyber"))] {
-                return Err(PakeError::InvalidInput);
+                return Err(PakeError::ParameterMismatch);
             }
         }
     }
     
-    let _ = hic::hic_eval(msg1, &(*pk), pw, sid);
+    hic::hic_eval(msg1, &(*pk), pw, sid)?;
     Ok(())
 }
 
+/// Name:  init_start_in
+///
+/// Description: init_start with caller-provided scratch memory and a keypair closure that writes
+///  the keys in place, so that neither the HIC nor the keypair puts key-sized arrays on the stack
+/// 
+/// Results:     - [u8] msg1: the outgoing message (of length MSG1_LEN)
+///              - [u8] pk: the pk part of the state (of length KYBER_PUBLICKEYBYTES)
+///              - [u8] sk: the sk part of the state (of length KYBER_SECRETKEYBYTES)
+///
+/// Arguments:   - PakeWorkspace ws: scratch memory
+///              - [u8] pw: the input pw (of length KYBER_SYMBYTES)
+///              - [u8] sid: the input sid (of length KYBER_SYMBYTES)
+///              - rng: the RNG to be used by keypair
+///              - keypair: closure that writes pk and sk
+#[allow(clippy::too_many_arguments)]
+pub fn init_start_in<R,F>(
+    ws: &mut PakeWorkspace,
+    msg1: &mut [u8;MSG1_LEN],
+    pk: &mut [u8;KYBER_PUBLICKEYBYTES],
+    sk: &mut [u8;KYBER_SECRETKEYBYTES],
+    pw: &[u8;KYBER_SYMBYTES],
+    sid: &[u8;KYBER_SYMBYTES],
+    rng: &mut R,
+    mut keypair: F
+) -> Result<(), PakeError>
+where
+    R: RngCore + CryptoRng,
+    F: FnMut(&mut [u8;KYBER_PUBLICKEYBYTES],&mut [u8;KYBER_SECRETKEYBYTES],&mut R) -> Result<(), PakeError>
+{
+    keypair(pk, sk, rng)?;
+    hic_eval_in(&mut ws.hic, msg1, pk, pw, sid)
+}
+
 /// Name:  resp
 ///
 /// Description: First message from initiator
@@ -77,51 +139,158 @@ pub fn resp<R,F>(
 ) -> Result<(), PakeError>
 where
     R: RngCore + CryptoRng,
-    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> ([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES])
+    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>
 {
-    let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
-    let mut keytag = [0u8;2*KYBER_SYMBYTES];
-    let mut hashin = [0u8;2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES+1];
-    // let mut init_tag = [0u8;KYBER_SYMBYTES];
-
-    let _ = hic_inv(&mut pk, msg1, pw, sid);
+    let mut ws = PakeWorkspace::new();
     match maybe_encapsulate {
         Some(mut encapsulate) => {
-            let (ct, ss) = encapsulate(&(pk), _rng);
-            msg2[KYBER_SYMBYTES..].copy_from_slice(&ct);
-            hashin[..KYBER_SYMBYTES].copy_from_slice(&ss);
+            resp_in(&mut ws, key, msg2, init_tag, msg1, pw, sid, _rng, |ct, ss, pk, rng| {
+                let (kem_ct, kem_ss) = encapsulate(pk, rng)?;
+                ct.copy_from_slice(&kem_ct);
+                ss.copy_from_slice(&kem_ss);
+                Ok(())
+            })
         }
         None => {
             #[cfg(feature = "default-kyber")] {
-                let (ct,ss) = pqc_kyber::encapsulate(&pk, _rng).unwrap();
-                msg2[KYBER_SYMBYTES..].copy_from_slice(&ct);
-                hashin[..KYBER_SYMBYTES].copy_from_slice(&ss);
+                resp_in(&mut ws, key, msg2, init_tag, msg1, pw, sid, _rng, |ct, ss, pk, rng| {
+                    let (kem_ct, kem_ss) = pqc_kyber::encapsulate(pk, rng)?;
+                    ct.copy_from_slice(&kem_ct);
+                    ss.copy_from_slice(&kem_ss);
+                    Ok(())
+                })
             }
             #[cfg(not(feature = "default-kyber"))] {
-                return Err(PakeError::InvalidInput);
+                Err(PakeError::ParameterMismatch)
             }
-            
         }
     }
-    
+}
+
+/// Name:  resp_in
+///
+/// Description: resp with caller-provided scratch memory and an encapsulation closure that writes
+///  the ciphertext straight into msg2. The transcript is hashed as it is produced.
+/// 
+/// Results:     - [u8] key: the output key (of length KYBER_SYMBYTES)
+///              - [u8] msg2: the output message (of length MSG2_LEN)
+///              - [u8] init_tag: the expected initiator to responder key confirmation tag (of length KYBER_SYMBYTES)
+///
+/// Arguments:   - PakeWorkspace ws: scratch memory
+///              - [u8] msg1: the input message (of length MSG1_LEN)
+///              - [u8] pw: the pw (of length KYBER_SYMBYTES)
+///              - [u8] sid: the input sid (of length KYBER_SYMBYTES)
+///              - rng: the RNG to be used by encapsulate
+///              - encapsulate: closure that writes the ciphertext and the shared secret for pk
+#[allow(clippy::too_many_arguments)]
+pub fn resp_in<R,F>(
+    ws: &mut PakeWorkspace,
+    key: &mut [u8;KYBER_SYMBYTES],
+    msg2: &mut [u8;MSG2_LEN],
+    init_tag: &mut [u8;KYBER_SYMBYTES],
+    msg1: &[u8;MSG1_LEN],
+    pw: &[u8;KYBER_SYMBYTES],
+    sid: &[u8;KYBER_SYMBYTES],
+    rng: &mut R,
+    mut encapsulate: F
+) -> Result<(), PakeError>
+where
+    R: RngCore + CryptoRng,
+    F: FnMut(&mut [u8;KYBER_CIPHERTEXTBYTES],&mut [u8;KYBER_SYMBYTES],&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<(), PakeError>
+{
+    let mut ss = [0u8;KYBER_SYMBYTES];
+    let mut keytag = [0u8;2*KYBER_SYMBYTES];
+    let (mut hash_key, mut hash_tag) = (HashG::new(), HashH::new());
+    let PakeWorkspace { hic, pk } = ws;
+
+    // Rejects msg1 with PakeError::MalformedMessage if it is not a canonical encoding
+    hic_inv_in(hic, pk, msg1, pw, sid)?;
+    encapsulate(ciphertext_mut(msg2), &mut ss, pk, rng)?;
+
     // Tag = H(K_s,sid,pk,apk,cph)
-    hashin[KYBER_SYMBYTES..2*KYBER_SYMBYTES].copy_from_slice(sid);
-    hashin[2*KYBER_SYMBYTES..2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES].copy_from_slice(&pk);
-    hashin[2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES..2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES].copy_from_slice(msg1);
-    hashin[2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES..2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES].copy_from_slice(&msg2[KYBER_SYMBYTES..KYBER_SYMBYTES+KYBER_CIPHERTEXTBYTES]);
+    hash_key.update(&ss);
+    hash_tag.update(&ss);
+    for part in transcript(sid, pk, msg1, msg2) {
+        hash_key.update(part);
+        hash_tag.update(part);
+    }
+
     // Responder to initiator key confirmation tag
-    hashin[2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES] = 0;
-    hash_g(&mut keytag, &hashin, 2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES+1);
+    hash_key.update(&[0]);
+    hash_key.finalize(&mut keytag);
     key[..KYBER_SYMBYTES].copy_from_slice(&keytag[..KYBER_SYMBYTES]);
     msg2[..KYBER_SYMBYTES].copy_from_slice(&keytag[KYBER_SYMBYTES..2*KYBER_SYMBYTES]);
 
     // Pre calculate initiator to responder key confirmation tag
-    hashin[2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES] = 1;
-    hash_h(init_tag, &hashin, 2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES+1);
+    hash_tag.update(&[1]);
+    hash_tag.finalize(init_tag);
 
     Ok(())
 }
 
+/// The ciphertext part of msg2
+fn ciphertext(msg2: &[u8;MSG2_LEN]) -> &[u8;KYBER_CIPHERTEXTBYTES] {
+    let (_, ct) = msg2.split_at(KYBER_SYMBYTES);
+    // MSG2_LEN = KYBER_SYMBYTES + KYBER_CIPHERTEXTBYTES
+    ct.try_into().unwrap()
+}
+
+/// The ciphertext part of msg2, mutable
+fn ciphertext_mut(msg2: &mut [u8;MSG2_LEN]) -> &mut [u8;KYBER_CIPHERTEXTBYTES] {
+    let (_, ct) = msg2.split_at_mut(KYBER_SYMBYTES);
+    // MSG2_LEN = KYBER_SYMBYTES + KYBER_CIPHERTEXTBYTES
+    ct.try_into().unwrap()
+}
+
+/// Name:  transcript
+///
+/// Description: The part of the transcript that follows K_s, sid || pk || apk || cph, in the
+///  order in which it is hashed into the key and the key confirmation tags. Both sides stream
+///  it into the hashes instead of copying it into one buffer.
+///
+/// Arguments:   - [u8] sid: the input sid (of length KYBER_SYMBYTES)
+///              - [u8] pk: the KEM public key (of length KYBER_PUBLICKEYBYTES)
+///              - [u8] msg1: the encrypted public key (of length MSG1_LEN)
+///              - [u8] msg2: the responder's message (of length MSG2_LEN)
+fn transcript<'a>(
+    sid: &'a [u8;KYBER_SYMBYTES],
+    pk: &'a [u8;KYBER_PUBLICKEYBYTES],
+    msg1: &'a [u8;MSG1_LEN],
+    msg2: &'a [u8;MSG2_LEN]
+) -> [&'a [u8];4] {
+    [sid, pk, msg1, ciphertext(msg2)]
+}
+
+/// Name:  init_end_decapsulate
+///
+/// Description: Decapsulates the ciphertext part of msg2
+/// 
+/// Arguments:   - [u8] msg2: the input message (of length MSG2_LEN )
+///              - [u8] sk: the sk part of the state (of length KYBER_SECRETKEYBYTES)
+///              - decapsulate: closure that contains the decapsulation logic
+///
+/// Returns the shared secret K_s
+fn init_end_decapsulate<F>(
+    msg2: &[u8;MSG2_LEN],
+    sk: &[u8;KYBER_SECRETKEYBYTES],
+    maybe_decapsulate: Option<F>
+) -> Result<[u8;KYBER_SYMBYTES], PakeError>
+where
+    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
+{
+    match maybe_decapsulate {
+        Some(mut decapsulate) => decapsulate(ciphertext(msg2), sk),
+        None => {
+            #[cfg(feature = "default-kyber")] {
+                Ok(pqc_kyber::decapsulate(ciphertext(msg2), sk)?)
+            }
+            #[cfg(not(feature = "default-kyber"))] {
+                Err(PakeError::ParameterMismatch)
+            }
+        }
+    }
+}
+
 /*************************************************
 * Name:        initEnd
 *
@@ -149,15 +318,17 @@ where
 /// Description: Last stage of initiator
 /// 
 /// Results:     - [u8] key: the output key (of length KYBER_SYMBYTES)
+///              - [u8] init_tag: the initiator to responder key confirmation tag (of length KYBER_SYMBYTES)
 ///
 /// Arguments:   - [u8] msg2: the input message (of length MSG2_LEN )
-/// Arguments:   - [u8] msg1: the previously sent message (of length MSG1_LEN )
+///              - [u8] msg1: the previously sent message (of length MSG1_LEN )
 ///              - [u8] pk: the pk part of the state (of length KYBER_PUBLICKEYBYTES)
 ///              - [u8] sk: the sk part of the state (of length KYBER_SECRETKEYBYTES)
 ///              - [u8] sid: the input sid (of length KYBER_SYMBYTES)
 ///              - decapsulate: closure that contains the decapsulation logic
 /// 
-/// Return values: 0 if ok, -1 if not ok
+/// Return values: Ok if the responder's tag verifies, PakeError::AuthenticationFailure
+///                otherwise, in which case key and init_tag are zeroed
 pub fn init_end<F>(
     key: &mut [u8;KYBER_SYMBYTES],
     init_tag: &mut [u8; KYBER_SYMBYTES],
@@ -167,40 +338,26 @@ pub fn init_end<F>(
     sk: &[u8;KYBER_SECRETKEYBYTES],
     sid: &[u8;KYBER_SYMBYTES],
     maybe_decapsulate: Option<F>
-) -> Result<u8, PakeError>
+) -> Result<(), PakeError>
 where
-    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> [u8;KYBER_SYMBYTES]
+    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
 {
     let mut keytag = [0u8;2*KYBER_SYMBYTES];
-    let mut hashin = [0u8;2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES+1];
+    let (mut hash_key, mut hash_tag) = (HashG::new(), HashH::new());
 
-    let mut ct = [0u8;KYBER_CIPHERTEXTBYTES];
-    ct.copy_from_slice(&(*msg2)[KYBER_SYMBYTES..]);
+    let ss = init_end_decapsulate(msg2, sk, maybe_decapsulate)?;
 
-    match maybe_decapsulate {
-        Some(mut decapsulate) => {
-            let decaps = decapsulate(&ct, sk);
-            hashin[..KYBER_SYMBYTES].copy_from_slice(&decaps);
-        }
-        None => {
-            #[cfg(feature = "default-kyber")] {
-                let decaps = pqc_kyber::decapsulate(&ct, sk).unwrap();
-                hashin[..KYBER_SYMBYTES].copy_from_slice(&decaps);
-            }
-            #[cfg(not(feature = "default-kyber"))] {
-                return Err(PakeError::InvalidInput);
-            }
-        }
+    // Tag = H(K_s,sid,pk,apk,cph)
+    hash_key.update(&ss);
+    hash_tag.update(&ss);
+    for part in transcript(sid, pk, msg1, msg2) {
+        hash_key.update(part);
+        hash_tag.update(part);
     }
 
-    // Tag = H(K_s,sid,pk,apk,cph)
-    hashin[KYBER_SYMBYTES..2*KYBER_SYMBYTES].copy_from_slice(sid);
-    hashin[2*KYBER_SYMBYTES..2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES].copy_from_slice(pk);
-    hashin[2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES..2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES].copy_from_slice(msg1);
-    hashin[2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES..2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES].copy_from_slice(&msg2[KYBER_SYMBYTES..KYBER_SYMBYTES+KYBER_CIPHERTEXTBYTES]);
     // Responder to initiator key confirmation tag
-    hashin[2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES] = 0;
-    hash_g(&mut keytag, &hashin, 2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES+1);
+    hash_key.update(&[0]);
+    hash_key.finalize(&mut keytag);
 
     // Check tag
     let result = verify(&keytag[KYBER_SYMBYTES..], msg2, KYBER_SYMBYTES);
@@ -209,17 +366,105 @@ where
     cmov(key, &keytag, KYBER_SYMBYTES, (result&1)^1 as u8);
 
     // Calculate initiator to responder key confirmation tag
-    hashin[2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES] = 1;
-    hash_h(init_tag, &hashin, 2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES+1);
+    hash_tag.update(&[1]);
+    hash_tag.finalize(init_tag);
+
+    // Never hand out key material for a session that failed to authenticate
+    if result != 0 {
+        key.fill(0);
+        init_tag.fill(0);
+        return Err(PakeError::AuthenticationFailure);
+    }
+
+    Ok(())
+}
+
+/// Name:  init_end_implicit
+///
+/// Description: Last stage of initiator with implicit rejection. If the responder's tag does not
+///  verify, the output key is a pseudorandom function of the secret rejection seed and the
+///  transcript (as in ML-KEM decapsulation) instead of an error, so the application layer of
+///  the initiator cannot be used as a tag-check oracle. The key confirmation tag is replaced in
+///  the same way, so failure is only detected by the responder through key confirmation.
+///  Runs in constant time with respect to the tag check.
+/// 
+/// Results:     - [u8] key: the output key (of length KYBER_SYMBYTES)
+///              - [u8] init_tag: the initiator to responder key confirmation tag (of length KYBER_SYMBYTES)
+///
+/// Arguments:   - [u8] msg2: the input message (of length MSG2_LEN )
+///              - [u8] msg1: the previously sent message (of length MSG1_LEN )
+///              - [u8] pk: the pk part of the state (of length KYBER_PUBLICKEYBYTES)
+///              - [u8] sk: the sk part of the state (of length KYBER_SECRETKEYBYTES)
+///              - [u8] sid: the input sid (of length KYBER_SYMBYTES)
+///              - [u8] z: the secret rejection seed (of length KYBER_SYMBYTES)
+///              - decapsulate: closure that contains the decapsulation logic
+pub fn init_end_implicit<F>(
+    key: &mut [u8;KYBER_SYMBYTES],
+    init_tag: &mut [u8; KYBER_SYMBYTES],
+    msg2: &[u8;MSG2_LEN],
+    msg1: &[u8;MSG1_LEN],
+    pk: &[u8;KYBER_PUBLICKEYBYTES],
+    sk: &[u8;KYBER_SECRETKEYBYTES],
+    sid: &[u8;KYBER_SYMBYTES],
+    z: &[u8;KYBER_SYMBYTES],
+    maybe_decapsulate: Option<F>
+) -> Result<(), PakeError>
+where
+    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
+{
+    let mut keytag = [0u8;2*KYBER_SYMBYTES];
+    let mut rejkey = [0u8;KYBER_SYMBYTES];
+    let mut rejtag = [0u8;KYBER_SYMBYTES];
+    let (mut hash_key, mut hash_tag) = (HashG::new(), HashH::new());
+    let (mut kdf_key, mut kdf_tag) = (Kdf::new(), Kdf::new());
+
+    let ss = init_end_decapsulate(msg2, sk, maybe_decapsulate)?;
+
+    // Tag = H(K_s,sid,pk,apk,cph), rejection key and tag = KDF(z,sid,pk,apk,cph)
+    hash_key.update(&ss);
+    hash_tag.update(&ss);
+    kdf_key.update(z);
+    kdf_tag.update(z);
+    for part in transcript(sid, pk, msg1, msg2) {
+        hash_key.update(part);
+        hash_tag.update(part);
+        kdf_key.update(part);
+        kdf_tag.update(part);
+    }
+
+    // Responder to initiator key confirmation tag
+    hash_key.update(&[0]);
+    hash_key.finalize(&mut keytag);
+
+    // Check tag
+    let result = verify(&keytag[KYBER_SYMBYTES..], msg2, KYBER_SYMBYTES);
+
+    // Calculate initiator to responder key confirmation tag
+    hash_tag.update(&[1]);
+    hash_tag.finalize(init_tag);
+
+    // The rejection tag must be replaced too, otherwise a modified responder tag would go
+    // unnoticed by the responder
+    kdf_key.update(&[2]);
+    kdf_key.finalize(&mut rejkey);
+    kdf_tag.update(&[3]);
+    kdf_tag.finalize(&mut rejtag);
 
-    Ok(result)
+    // Overwrite the key and tag with the rejection values if the tag is bad
+    key.copy_from_slice(&keytag[..KYBER_SYMBYTES]);
+    cmov(key, &rejkey, KYBER_SYMBYTES, result);
+    cmov(init_tag, &rejtag, KYBER_SYMBYTES, result);
+
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
 
-    use rand::{rngs::ThreadRng, RngCore};
+    use proptest::prelude::*;
+    use rand::{rngs::{StdRng, ThreadRng}, RngCore, SeedableRng};
     use crate::params::*;
+    use crate::testing::*;
     use super::*;
 
     #[test]
@@ -240,29 +485,12 @@ mod tests {
         rng.fill_bytes(&mut pw);
         rng.fill_bytes(&mut sid);
 
-        let keypair_func = | rng: &mut rand::rngs::ThreadRng | {
-            let keypair = pqc_kyber::keypair(rng).unwrap();
-            let pk = keypair.public;
-            let sk = keypair.secret;
-            (pk,sk)
-        };
-
-        let encapsulate_func= | pk: &[u8;KYBER_PUBLICKEYBYTES], rng: &mut rand::rngs::ThreadRng | {
-            let (ct,ss) = pqc_kyber::encapsulate(pk, rng).unwrap();
-            (ct,ss)
-        };
-
-        let decapsulate_func= | ct: &[u8;KYBER_CIPHERTEXTBYTES], sk: &[u8;KYBER_SECRETKEYBYTES] | {
-            let ss = pqc_kyber::decapsulate(ct, sk).unwrap();
-            ss
-        };
-
         // msg1 is the encrypted public key Alice sends to Bob
         assert_eq!(init_start(&mut msg1, &mut pk, &mut sk, &pw, &sid, &mut rng, Some(keypair_func)), Ok(()));
         // key_a is the shared secret Bob derived, and msg2 is the ciphertext containing that secret
         assert_eq!(resp(&mut key_a, &mut msg2, &mut init_tag, &msg1, &pw, &sid, &mut rng, Some(encapsulate_func)), Ok(()));
         // last step receives msg2, the ciphertext containing the shared secret, and outputs key_b, the shared secret =key_a
-        assert_eq!(init_end(&mut key_b, &mut init_tag_2, &msg2, &msg1, &pk, &sk, &sid, Some(decapsulate_func)), Ok(0));
+        assert_eq!(init_end(&mut key_b, &mut init_tag_2, &msg2, &msg1, &pk, &sk, &sid, Some(decapsulate_func)), Ok(()));
 
         assert_eq!(key_a, key_b);
 
@@ -270,6 +498,107 @@ mod tests {
         assert_eq!(init_tag,init_tag_2);
     }
 
+    #[test]
+    fn test_init_end_wrong_password() {
+        let mut sid = [0u8;KYBER_SSBYTES];
+        let mut pw_a = [0u8;KYBER_SSBYTES];
+        let mut pw_b = [0u8;KYBER_SSBYTES];
+        let mut sk = [0u8;KYBER_SECRETKEYBYTES];
+        let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
+        let mut key_a = [0u8;KYBER_SSBYTES];
+        // Garbage in the output buffers must not survive a failed session either
+        let mut key_b = [0xAAu8;KYBER_SSBYTES];
+        let mut msg1 = [0u8;MSG1_LEN];
+        let mut msg2 = [0u8;MSG2_LEN];
+        let mut init_tag = [0u8;KYBER_SYMBYTES];
+        let mut init_tag_2 = [0xAAu8;KYBER_SYMBYTES];
+
+        let mut rng = rand::thread_rng();
+
+        rng.fill_bytes(&mut pw_a);
+        rng.fill_bytes(&mut pw_b);
+        rng.fill_bytes(&mut sid);
+
+        assert_eq!(init_start(&mut msg1, &mut pk, &mut sk, &pw_a, &sid, &mut rng, Some(keypair_func)), Ok(()));
+        assert_eq!(resp(&mut key_a, &mut msg2, &mut init_tag, &msg1, &pw_b, &sid, &mut rng, Some(encapsulate_func)), Ok(()));
+        assert_eq!(init_end(&mut key_b, &mut init_tag_2, &msg2, &msg1, &pk, &sk, &sid, Some(decapsulate_func)), Err(PakeError::AuthenticationFailure));
+
+        assert_eq!(key_b, [0u8;KYBER_SSBYTES]);
+        assert_eq!(init_tag_2, [0u8;KYBER_SYMBYTES]);
+        assert_ne!(key_a, key_b);
+    }
+
+    #[test]
+    fn test_init_end_implicit_rejection() {
+        let mut sid = [0u8;KYBER_SSBYTES];
+        let mut pw = [0u8;KYBER_SSBYTES];
+        let mut z = [0u8;KYBER_SYMBYTES];
+        let mut sk = [0u8;KYBER_SECRETKEYBYTES];
+        let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
+        let mut key_a = [0u8;KYBER_SSBYTES];
+        let mut key_b = [0u8;KYBER_SSBYTES];
+        let mut key_c = [0u8;KYBER_SSBYTES];
+        let mut msg1 = [0u8;MSG1_LEN];
+        let mut msg2 = [0u8;MSG2_LEN];
+        let mut init_tag = [0u8;KYBER_SYMBYTES];
+        let mut init_tag_2 = [0u8;KYBER_SYMBYTES];
+
+        let mut rng = rand::thread_rng();
+
+        rng.fill_bytes(&mut pw);
+        rng.fill_bytes(&mut sid);
+        rng.fill_bytes(&mut z);
+
+        assert_eq!(init_start(&mut msg1, &mut pk, &mut sk, &pw, &sid, &mut rng, Some(keypair_func)), Ok(()));
+        assert_eq!(resp(&mut key_a, &mut msg2, &mut init_tag, &msg1, &pw, &sid, &mut rng, Some(encapsulate_func)), Ok(()));
+
+        // Honest run behaves like init_end
+        assert_eq!(init_end_implicit(&mut key_b, &mut init_tag_2, &msg2, &msg1, &pk, &sk, &sid, &z, Some(decapsulate_func)), Ok(()));
+        assert_eq!(key_a, key_b);
+        assert_eq!(init_tag, init_tag_2);
+
+        // A bad tag still yields a key, but an unrelated one, and the responder rejects the confirmation tag
+        msg2[0] ^= 1;
+        assert_eq!(init_end_implicit(&mut key_b, &mut init_tag_2, &msg2, &msg1, &pk, &sk, &sid, &z, Some(decapsulate_func)), Ok(()));
+        assert_ne!(key_a, key_b);
+        assert_ne!(key_b, [0u8;KYBER_SSBYTES]);
+        assert_ne!(init_tag, init_tag_2);
+
+        // The rejection key is deterministic in the seed and the transcript
+        assert_eq!(init_end_implicit(&mut key_c, &mut init_tag_2, &msg2, &msg1, &pk, &sk, &sid, &z, Some(decapsulate_func)), Ok(()));
+        assert_eq!(key_b, key_c);
+        z[0] ^= 1;
+        assert_eq!(init_end_implicit(&mut key_c, &mut init_tag_2, &msg2, &msg1, &pk, &sk, &sid, &z, Some(decapsulate_func)), Ok(()));
+        assert_ne!(key_b, key_c);
+    }
+
+    #[test]
+    fn test_resp_rejects_noncanonical_msg1() {
+        let mut sid = [0u8;KYBER_SSBYTES];
+        let mut pw = [0u8;KYBER_SSBYTES];
+        let mut sk = [0u8;KYBER_SECRETKEYBYTES];
+        let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
+        let mut key = [0u8;KYBER_SSBYTES];
+        let mut msg1 = [0u8;MSG1_LEN];
+        let mut msg2 = [0u8;MSG2_LEN];
+        let mut init_tag = [0u8;KYBER_SYMBYTES];
+
+        let mut rng = rand::thread_rng();
+
+        rng.fill_bytes(&mut pw);
+        rng.fill_bytes(&mut sid);
+
+        assert_eq!(init_start(&mut msg1, &mut pk, &mut sk, &pw, &sid, &mut rng, Some(keypair_func)), Ok(()));
+
+        // First coefficient of the masked t set to 0xFFF, which would otherwise be reduced to 0xFFF - q
+        msg1[0] = 0xFF;
+        msg1[1] |= 0x0F;
+
+        assert_eq!(resp(&mut key, &mut msg2, &mut init_tag, &msg1, &pw, &sid, &mut rng, Some(encapsulate_func)), Err(PakeError::MalformedMessage));
+        assert_eq!(key, [0u8;KYBER_SSBYTES]);
+        assert_eq!(msg2, [0u8;MSG2_LEN]);
+    }
+
     #[test]
     #[cfg(feature = "default-kyber")]
     fn test_chic_default_kyber() {
@@ -277,11 +606,12 @@ mod tests {
         let mut pw = [0u8;KYBER_SSBYTES];
         let mut sk = [0u8;KYBER_SECRETKEYBYTES];
         let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
-        let mut dec_pk = [0u8;KYBER_PUBLICKEYBYTES];
         let mut key_a = [0u8;KYBER_SSBYTES];
         let mut key_b = [0u8;KYBER_SSBYTES];
         let mut msg1 = [0u8;MSG1_LEN];
         let mut msg2 = [0u8;MSG2_LEN];
+        let mut init_tag = [0u8;KYBER_SYMBYTES];
+        let mut init_tag_2 = [0u8;KYBER_SYMBYTES];
         
         let mut rng = rand::thread_rng();
     
@@ -289,18 +619,135 @@ mod tests {
         rng.fill_bytes(&mut sid);
 
 
-        let keypair_none: Option<fn(&mut ThreadRng) -> ([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES])> = None;
-        let encapsulate_none: Option<fn(&[u8;KYBER_PUBLICKEYBYTES], &mut ThreadRng) -> ([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES])> = None;
-        let decapsulate_none: Option<fn(&[u8;KYBER_CIPHERTEXTBYTES], &[u8;KYBER_SECRETKEYBYTES]) -> [u8;KYBER_SYMBYTES]> = None;
+        let keypair_none: Option<fn(&mut ThreadRng) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>> = None;
+        let encapsulate_none: Option<fn(&[u8;KYBER_PUBLICKEYBYTES], &mut ThreadRng) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>> = None;
+        let decapsulate_none: Option<fn(&[u8;KYBER_CIPHERTEXTBYTES], &[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>> = None;
 
         // msg1 is the encrypted public key Alice sends to Bob
         assert_eq!(init_start(&mut msg1, &mut pk, &mut sk, &pw, &sid, &mut rng, keypair_none), Ok(()));
         // key_a is the shared secret Bob derived, and msg2 is the ciphertext containing that secret
-        assert_eq!(resp(&mut key_a, &mut msg2, &mut dec_pk, &msg1, &pw, &sid, &mut rng, encapsulate_none), Ok(()));
+        assert_eq!(resp(&mut key_a, &mut msg2, &mut init_tag, &msg1, &pw, &sid, &mut rng, encapsulate_none), Ok(()));
         // last step receives msg2, the ciphertext containing the shared secret, and outputs key_b, the shared secret =key_a
-        assert_eq!(init_end(&mut key_b, &mut msg2, &msg1, &pk, &sk, &sid, decapsulate_none), Ok(0));
+        assert_eq!(init_end(&mut key_b, &mut init_tag_2, &msg2, &msg1, &pk, &sk, &sid, decapsulate_none), Ok(()));
         
         assert_eq!(key_a, key_b);
+        assert_eq!(init_tag, init_tag_2);
+    }
+
+
+    #[test]
+    fn test_transcript_format() {
+        // The streamed hashes equal the one-shot hashes of K_s || sid || pk || apk || cph || b
+        use crate::symmetric::{hash_g, kdf};
+        let mut rng = StdRng::seed_from_u64(5);
+        let (pw, sid, z) = ([1u8;KYBER_SYMBYTES], [2u8;KYBER_SYMBYTES], [3u8;KYBER_SYMBYTES]);
+        let mut msg1 = [0u8;MSG1_LEN];
+        let mut msg2 = [0u8;MSG2_LEN];
+        let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
+        let mut sk = [0u8;KYBER_SECRETKEYBYTES];
+        let (mut key, mut init_tag, mut ss) = ([0u8;KYBER_SYMBYTES], [0u8;KYBER_SYMBYTES], [0u8;KYBER_SYMBYTES]);
+
+        let encapsulate_func = |pk: &[u8;KYBER_PUBLICKEYBYTES], rng: &mut StdRng| {
+            let (ct, kem_ss) = pqc_kyber::encapsulate(pk, rng)?;
+            ss = kem_ss;
+            Ok((ct, kem_ss))
+        };
+        init_start(&mut msg1, &mut pk, &mut sk, &pw, &sid, &mut rng, Some(keypair_func)).unwrap();
+        resp(&mut key, &mut msg2, &mut init_tag, &msg1, &pw, &sid, &mut rng, Some(encapsulate_func)).unwrap();
+
+        let mut hashin = [0u8;2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES+KYBER_CIPHERTEXTBYTES+1];
+        let last = hashin.len()-1;
+        hashin[..KYBER_SYMBYTES].copy_from_slice(&ss);
+        hashin[KYBER_SYMBYTES..2*KYBER_SYMBYTES].copy_from_slice(&sid);
+        hashin[2*KYBER_SYMBYTES..2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES].copy_from_slice(&pk);
+        hashin[2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES..2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES].copy_from_slice(&msg1);
+        hashin[2*KYBER_SYMBYTES+2*KYBER_PUBLICKEYBYTES..last].copy_from_slice(&msg2[KYBER_SYMBYTES..]);
+        let mut keytag = [0u8;2*KYBER_SYMBYTES];
+        let mut expected_tag = [0u8;KYBER_SYMBYTES];
+        hash_g(&mut keytag, &hashin, hashin.len());
+        hashin[last] = 1;
+        hash_h(&mut expected_tag, &hashin, hashin.len());
+        assert_eq!(key, keytag[..KYBER_SYMBYTES]);
+        assert_eq!(msg2[..KYBER_SYMBYTES], keytag[KYBER_SYMBYTES..]);
+        assert_eq!(init_tag, expected_tag);
+
+        // Implicit rejection derives key and tag from z in place of K_s
+        let (mut rejkey, mut rejtag) = ([0u8;KYBER_SYMBYTES], [0u8;KYBER_SYMBYTES]);
+        hashin[..KYBER_SYMBYTES].copy_from_slice(&z);
+        hashin[last] = 2;
+        kdf(&mut rejkey, &hashin, hashin.len());
+        hashin[last] = 3;
+        kdf(&mut rejtag, &hashin, hashin.len());
+        msg2[0] ^= 1;
+        init_end_implicit(&mut key, &mut init_tag, &msg2, &msg1, &pk, &sk, &sid, &z, Some(decapsulate_func)).unwrap();
+        assert_eq!((key, init_tag), (rejkey, rejtag));
     }
 
-}
\ No newline at end of file
+    /// One run with the given inputs on each side, optionally flipping bits of msg1 or msg2 in
+    /// transit. Returns the responder's result and the initiator's result.
+    #[allow(clippy::type_complexity)]
+    fn run(
+        pw_a: &[u8;KYBER_SYMBYTES], sid_a: &[u8;KYBER_SYMBYTES],
+        pw_b: &[u8;KYBER_SYMBYTES], sid_b: &[u8;KYBER_SYMBYTES],
+        seed: u64, flip_msg1: Option<(usize, u8)>, flip_msg2: Option<(usize, u8)>
+    ) -> (Result<([u8;KYBER_SYMBYTES], [u8;KYBER_SYMBYTES]), PakeError>, Result<([u8;KYBER_SYMBYTES], [u8;KYBER_SYMBYTES]), PakeError>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut msg1 = [0u8;MSG1_LEN];
+        let mut msg2 = [0u8;MSG2_LEN];
+        let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
+        let mut sk = [0u8;KYBER_SECRETKEYBYTES];
+        let (mut key_b, mut tag_b) = ([0u8;KYBER_SYMBYTES], [0u8;KYBER_SYMBYTES]);
+        let (mut key_a, mut tag_a) = ([0u8;KYBER_SYMBYTES], [0u8;KYBER_SYMBYTES]);
+
+        init_start(&mut msg1, &mut pk, &mut sk, pw_a, sid_a, &mut rng, Some(keypair_func)).unwrap();
+        let mut msg1_received = msg1;
+        if let Some((i, mask)) = flip_msg1 {
+            msg1_received[i] ^= mask;
+        }
+        let resp_result = resp(&mut key_b, &mut msg2, &mut tag_b, &msg1_received, pw_b, sid_b, &mut rng, Some(encapsulate_func)).map(|_| (key_b, tag_b));
+        if let Some((i, mask)) = flip_msg2 {
+            msg2[i] ^= mask;
+        }
+        let init_result = init_end(&mut key_a, &mut tag_a, &msg2, &msg1, &pk, &sk, sid_a, Some(decapsulate_func)).map(|_| (key_a, tag_a));
+        (resp_result, init_result)
+    }
+
+    // Properties of the protocol at the parameter set this build was compiled for
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn prop_same_password_agrees(pw in any::<[u8;KYBER_SYMBYTES]>(), sid in any::<[u8;KYBER_SYMBYTES]>(), seed in any::<u64>()) {
+            let (b, a) = run(&pw, &sid, &pw, &sid, seed, None, None);
+            prop_assert_eq!(b.unwrap(), a.unwrap());
+        }
+
+        #[test]
+        fn prop_different_password_fails(pw_a in any::<[u8;KYBER_SYMBYTES]>(), pw_b in any::<[u8;KYBER_SYMBYTES]>(), sid in any::<[u8;KYBER_SYMBYTES]>(), seed in any::<u64>()) {
+            prop_assume!(pw_a != pw_b);
+            let (_, a) = run(&pw_a, &sid, &pw_b, &sid, seed, None, None);
+            prop_assert_eq!(a, Err(PakeError::AuthenticationFailure));
+        }
+
+        #[test]
+        fn prop_different_sid_fails(pw in any::<[u8;KYBER_SYMBYTES]>(), sid_a in any::<[u8;KYBER_SYMBYTES]>(), sid_b in any::<[u8;KYBER_SYMBYTES]>(), seed in any::<u64>()) {
+            prop_assume!(sid_a != sid_b);
+            let (_, a) = run(&pw, &sid_a, &pw, &sid_b, seed, None, None);
+            prop_assert_eq!(a, Err(PakeError::AuthenticationFailure));
+        }
+
+        #[test]
+        fn prop_modified_msg1_fails(pw in any::<[u8;KYBER_SYMBYTES]>(), sid in any::<[u8;KYBER_SYMBYTES]>(), seed in any::<u64>(), i in 0..MSG1_LEN, mask in 1..=255u8) {
+            // Either the responder rejects msg1 as malformed, or the initiator rejects msg2
+            let (b, a) = run(&pw, &sid, &pw, &sid, seed, Some((i, mask)), None);
+            prop_assert!(b == Err(PakeError::MalformedMessage) || a == Err(PakeError::AuthenticationFailure));
+        }
+
+        #[test]
+        fn prop_modified_msg2_fails(pw in any::<[u8;KYBER_SYMBYTES]>(), sid in any::<[u8;KYBER_SYMBYTES]>(), seed in any::<u64>(), i in 0..MSG2_LEN, mask in 1..=255u8) {
+            let (_, a) = run(&pw, &sid, &pw, &sid, seed, None, Some((i, mask)));
+            prop_assert_eq!(a, Err(PakeError::AuthenticationFailure));
+        }
+    }
+
+}
@@ -0,0 +1,425 @@
+//! Server-side credential records and stores.
+//!
+//! CHIC is a symmetric PAKE: the responder needs the same 32-byte pw as the initiator. Servers
+//! keep a CredentialRecord per account, holding pw as derived from the user's password together
+//! with everything the client needs to derive it again (algorithm, salt and cost parameters),
+//! and the parameter set the record was created for.
+
+use crate::error::{PakeError, StoreError};
+use crate::params::{KYBER_K, KYBER_SYMBYTES};
+
+/// Size of the salt of a credential record
+pub const SALT_BYTES: usize = KYBER_SYMBYTES;
+
+/// Size of the encoding of a credential record
+pub const RECORD_BYTES: usize = 2 + 12 + SALT_BYTES + KYBER_SYMBYTES;
+
+/// How pw is derived from the user's password
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PasswordHashAlgorithm {
+    /// pw is the password itself, which must be KYBER_SYMBYTES high-entropy bytes
+    Raw = 0,
+    /// pw = Argon2id(password, salt) with the record's cost parameters
+    Argon2id = 1,
+}
+
+impl PasswordHashAlgorithm {
+    fn from_u8(id: u8) -> Result<Self, PakeError> {
+        match id {
+            0 => Ok(PasswordHashAlgorithm::Raw),
+            1 => Ok(PasswordHashAlgorithm::Argon2id),
+            _ => Err(PakeError::ParameterMismatch),
+        }
+    }
+}
+
+/// Cost parameters of the password hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashParams {
+    /// Memory size in KiB
+    pub m_cost: u32,
+    /// Number of passes
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl Default for HashParams {
+    /// The Argon2id parameters recommended by OWASP
+    fn default() -> Self {
+        HashParams { m_cost: 19 * 1024, t_cost: 2, p_cost: 1 }
+    }
+}
+
+/// Credentials of one account
+#[derive(Clone, PartialEq, Eq)]
+pub struct CredentialRecord {
+    pub algorithm: PasswordHashAlgorithm,
+    /// KYBER_K of the parameter set the record is used with
+    pub param_set: u8,
+    pub params: HashParams,
+    pub salt: [u8; SALT_BYTES],
+    /// The pw input of the protocol
+    pub pw: [u8; KYBER_SYMBYTES],
+}
+
+impl core::fmt::Debug for CredentialRecord {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("CredentialRecord")
+            .field("algorithm", &self.algorithm)
+            .field("param_set", &self.param_set)
+            .field("params", &self.params)
+            .field("salt", &self.salt)
+            .field("pw", &"<redacted>")
+            .finish()
+    }
+}
+
+impl CredentialRecord {
+    /// Creates a record for the parameter set in use, deriving pw from password
+    pub fn new(password: &[u8], algorithm: PasswordHashAlgorithm, salt: &[u8; SALT_BYTES], params: HashParams) -> Result<Self, PakeError> {
+        let pw = derive_pw(password, algorithm, salt, &params)?;
+        Ok(CredentialRecord { algorithm, param_set: KYBER_K as u8, params, salt: *salt, pw })
+    }
+
+    /// Fails with PakeError::ParameterMismatch if the record was created for another parameter set
+    pub fn check_param_set(&self) -> Result<(), PakeError> {
+        if self.param_set as usize != KYBER_K {
+            return Err(PakeError::ParameterMismatch);
+        }
+        Ok(())
+    }
+
+    /// Encoding: algorithm || param_set || m_cost || t_cost || p_cost || salt || pw, integers little endian
+    pub fn to_bytes(&self) -> [u8; RECORD_BYTES] {
+        let mut out = [0u8; RECORD_BYTES];
+        out[0] = self.algorithm as u8;
+        out[1] = self.param_set;
+        out[2..6].copy_from_slice(&self.params.m_cost.to_le_bytes());
+        out[6..10].copy_from_slice(&self.params.t_cost.to_le_bytes());
+        out[10..14].copy_from_slice(&self.params.p_cost.to_le_bytes());
+        out[14..14 + SALT_BYTES].copy_from_slice(&self.salt);
+        out[14 + SALT_BYTES..].copy_from_slice(&self.pw);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PakeError> {
+        if bytes.len() != RECORD_BYTES {
+            return Err(PakeError::MalformedMessage);
+        }
+        let u32_at = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let mut salt = [0u8; SALT_BYTES];
+        let mut pw = [0u8; KYBER_SYMBYTES];
+        salt.copy_from_slice(&bytes[14..14 + SALT_BYTES]);
+        pw.copy_from_slice(&bytes[14 + SALT_BYTES..]);
+        Ok(CredentialRecord {
+            algorithm: PasswordHashAlgorithm::from_u8(bytes[0])?,
+            param_set: bytes[1],
+            params: HashParams { m_cost: u32_at(2), t_cost: u32_at(6), p_cost: u32_at(10) },
+            salt,
+            pw,
+        })
+    }
+}
+
+/// Name:  derive_pw
+///
+/// Description: Derives the pw input of the protocol from a password. Used by the server when
+///  registering an account and by the client once it knows the salt and cost parameters.
+///
+/// Arguments:   - [u8] password: the user's password
+///              - algorithm: the password hash algorithm
+///              - [u8] salt: the salt (of length SALT_BYTES)
+///              - params: the cost parameters
+///
+/// Returns pw (of length KYBER_SYMBYTES)
+pub fn derive_pw(password: &[u8], algorithm: PasswordHashAlgorithm, salt: &[u8; SALT_BYTES], params: &HashParams) -> Result<[u8; KYBER_SYMBYTES], PakeError> {
+    let mut pw = [0u8; KYBER_SYMBYTES];
+    match algorithm {
+        PasswordHashAlgorithm::Raw => {
+            if password.len() != KYBER_SYMBYTES {
+                return Err(StoreError::PasswordHashing.into());
+            }
+            pw.copy_from_slice(password);
+        }
+        PasswordHashAlgorithm::Argon2id => {
+            #[cfg(feature = "argon2")] {
+                let params = argon2::Params::new(params.m_cost, params.t_cost, params.p_cost, Some(KYBER_SYMBYTES))
+                    .map_err(|_| StoreError::PasswordHashing)?;
+                argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password_into(password, salt, &mut pw)
+                    .map_err(|_| StoreError::PasswordHashing)?;
+            }
+            #[cfg(not(feature = "argon2"))] {
+                let _ = (salt, params);
+                return Err(PakeError::ParameterMismatch);
+            }
+        }
+    }
+    Ok(pw)
+}
+
+/// Account registry of a server
+pub trait CredentialStore {
+    /// Adds an account, fails with StoreError::AccountExists if it is already registered
+    fn register(&mut self, username: &[u8], record: CredentialRecord) -> Result<(), StoreError>;
+
+    /// Returns the record of an account, or None if it is not registered
+    fn lookup(&self, username: &[u8]) -> Result<Option<CredentialRecord>, StoreError>;
+
+    /// Replaces the record of an account, fails with StoreError::UnknownAccount if it is not registered
+    fn change_password(&mut self, username: &[u8], record: CredentialRecord) -> Result<(), StoreError>;
+
+    /// Removes an account, fails with StoreError::UnknownAccount if it is not registered
+    fn remove(&mut self, username: &[u8]) -> Result<(), StoreError>;
+}
+
+/// Credential store kept in memory
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct MemoryCredentialStore {
+    records: std::collections::HashMap<std::vec::Vec<u8>, CredentialRecord>,
+}
+
+#[cfg(feature = "std")]
+impl MemoryCredentialStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(feature = "std")]
+impl CredentialStore for MemoryCredentialStore {
+    fn register(&mut self, username: &[u8], record: CredentialRecord) -> Result<(), StoreError> {
+        if self.records.contains_key(username) {
+            return Err(StoreError::AccountExists);
+        }
+        self.records.insert(username.to_vec(), record);
+        Ok(())
+    }
+
+    fn lookup(&self, username: &[u8]) -> Result<Option<CredentialRecord>, StoreError> {
+        Ok(self.records.get(username).cloned())
+    }
+
+    fn change_password(&mut self, username: &[u8], record: CredentialRecord) -> Result<(), StoreError> {
+        match self.records.get_mut(username) {
+            Some(old) => {
+                *old = record;
+                Ok(())
+            }
+            None => Err(StoreError::UnknownAccount),
+        }
+    }
+
+    fn remove(&mut self, username: &[u8]) -> Result<(), StoreError> {
+        self.records.remove(username).map(|_| ()).ok_or(StoreError::UnknownAccount)
+    }
+}
+
+/// Credential store backed by a file, kept in memory and rewritten on every change.
+///
+/// The file is a sequence of entries username length (u16 little endian) || username || record.
+/// Updates are written to a temporary file that is then renamed over the store, so a crash
+/// leaves either the old or the new version. The records are password-equivalent, so on unix the
+/// file is created readable by its owner only.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct FileCredentialStore {
+    path: std::path::PathBuf,
+    memory: MemoryCredentialStore,
+}
+
+#[cfg(feature = "std")]
+impl FileCredentialStore {
+    /// Opens the store at path, creating an empty one if the file does not exist
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, StoreError> {
+        let path = path.as_ref().to_path_buf();
+        let mut memory = MemoryCredentialStore::new();
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => std::vec::Vec::new(),
+            Err(_) => return Err(StoreError::Storage),
+        };
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            if rest.len() < 2 {
+                return Err(StoreError::Storage);
+            }
+            let len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+            if rest.len() < 2 + len + RECORD_BYTES {
+                return Err(StoreError::Storage);
+            }
+            let record = CredentialRecord::from_bytes(&rest[2 + len..2 + len + RECORD_BYTES]).map_err(|_| StoreError::Storage)?;
+            memory.register(&rest[2..2 + len], record).map_err(|_| StoreError::Storage)?;
+            rest = &rest[2 + len + RECORD_BYTES..];
+        }
+        Ok(FileCredentialStore { path, memory })
+    }
+
+    fn persist(&self) -> Result<(), StoreError> {
+        use std::io::Write;
+        let mut data = std::vec::Vec::new();
+        for (username, record) in &self.memory.records {
+            data.extend_from_slice(&(username.len() as u16).to_le_bytes());
+            data.extend_from_slice(username);
+            data.extend_from_slice(&record.to_bytes());
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        // Left over by a crash during an earlier update
+        let _ = std::fs::remove_file(&tmp);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(&tmp).map_err(|_| StoreError::Storage)?;
+        file.write_all(&data).and_then(|_| file.sync_all()).map_err(|_| StoreError::Storage)?;
+        std::fs::rename(&tmp, &self.path).map_err(|_| StoreError::Storage)?;
+
+        // The rename is only durable once the directory entry is
+        #[cfg(unix)] {
+            let dir = match self.path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => std::path::Path::new("."),
+            };
+            std::fs::File::open(dir).and_then(|dir| dir.sync_all()).map_err(|_| StoreError::Storage)?;
+        }
+        Ok(())
+    }
+
+    /// Applies a change to the in-memory copy and persists it, rolling back if writing fails
+    fn update<F>(&mut self, username: &[u8], change: F) -> Result<(), StoreError>
+    where
+        F: FnOnce(&mut MemoryCredentialStore) -> Result<(), StoreError>
+    {
+        let old = self.memory.records.get(username).cloned();
+        change(&mut self.memory)?;
+        if let Err(e) = self.persist() {
+            match old {
+                Some(record) => self.memory.records.insert(username.to_vec(), record),
+                None => self.memory.records.remove(username),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl CredentialStore for FileCredentialStore {
+    fn register(&mut self, username: &[u8], record: CredentialRecord) -> Result<(), StoreError> {
+        if username.len() > u16::MAX as usize {
+            return Err(StoreError::Storage);
+        }
+        self.update(username, |m| m.register(username, record))
+    }
+
+    fn lookup(&self, username: &[u8]) -> Result<Option<CredentialRecord>, StoreError> {
+        self.memory.lookup(username)
+    }
+
+    fn change_password(&mut self, username: &[u8], record: CredentialRecord) -> Result<(), StoreError> {
+        self.update(username, |m| m.change_password(username, record))
+    }
+
+    fn remove(&mut self, username: &[u8]) -> Result<(), StoreError> {
+        self.update(username, |m| m.remove(username))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn record(byte: u8) -> CredentialRecord {
+        CredentialRecord::new(&[byte; KYBER_SYMBYTES], PasswordHashAlgorithm::Raw, &[byte; SALT_BYTES], HashParams::default()).unwrap()
+    }
+
+    #[test]
+    fn test_record_encoding() {
+        let r = record(1);
+        assert_eq!(CredentialRecord::from_bytes(&r.to_bytes()), Ok(r.clone()));
+        assert_eq!(CredentialRecord::from_bytes(&r.to_bytes()[1..]), Err(PakeError::MalformedMessage));
+        let mut bad = r.to_bytes();
+        bad[0] = 0xFF;
+        assert_eq!(CredentialRecord::from_bytes(&bad), Err(PakeError::ParameterMismatch));
+    }
+
+    #[test]
+    fn test_param_set() {
+        let mut r = record(1);
+        assert_eq!(r.check_param_set(), Ok(()));
+        r.param_set ^= 1;
+        assert_eq!(r.check_param_set(), Err(PakeError::ParameterMismatch));
+    }
+
+    #[test]
+    #[cfg(feature = "argon2")]
+    fn test_argon2id() {
+        let params = HashParams { m_cost: 64, t_cost: 1, p_cost: 1 };
+        let salt = [7u8; SALT_BYTES];
+        let r = CredentialRecord::new(b"correct horse", PasswordHashAlgorithm::Argon2id, &salt, params).unwrap();
+        assert_eq!(derive_pw(b"correct horse", r.algorithm, &r.salt, &r.params), Ok(r.pw));
+        assert_ne!(derive_pw(b"battery staple", r.algorithm, &r.salt, &r.params), Ok(r.pw));
+        assert_ne!(derive_pw(b"correct horse", r.algorithm, &[8u8; SALT_BYTES], &r.params), Ok(r.pw));
+        let bad = HashParams { m_cost: 0, ..params };
+        assert_eq!(derive_pw(b"correct horse", r.algorithm, &salt, &bad), Err(PakeError::Store(StoreError::PasswordHashing)));
+    }
+
+    #[cfg(feature = "std")]
+    fn exercise<S: CredentialStore>(store: &mut S) {
+        assert_eq!(store.lookup(b"alice"), Ok(None));
+        assert_eq!(store.register(b"alice", record(1)), Ok(()));
+        assert_eq!(store.register(b"alice", record(2)), Err(StoreError::AccountExists));
+        assert_eq!(store.lookup(b"alice"), Ok(Some(record(1))));
+        assert_eq!(store.change_password(b"alice", record(3)), Ok(()));
+        assert_eq!(store.lookup(b"alice"), Ok(Some(record(3))));
+        assert_eq!(store.change_password(b"bob", record(3)), Err(StoreError::UnknownAccount));
+        assert_eq!(store.register(b"bob", record(4)), Ok(()));
+        assert_eq!(store.remove(b"bob"), Ok(()));
+        assert_eq!(store.remove(b"bob"), Err(StoreError::UnknownAccount));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_memory_store() {
+        exercise(&mut MemoryCredentialStore::new());
+        assert!(!format!("{:?}", record(1)).contains("pw: ["));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_file_store() {
+        let path = std::env::temp_dir().join(format!("chic-credentials-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut store = FileCredentialStore::open(&path).unwrap();
+        exercise(&mut store);
+
+        // A temporary file left over by a crash does not block updates
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, b"stale").unwrap();
+        assert_eq!(store.register(b"carol", record(5)), Ok(()));
+
+        // Reopening sees every change
+        let store = FileCredentialStore::open(&path).unwrap();
+        assert_eq!(store.lookup(b"alice"), Ok(Some(record(3))));
+        assert_eq!(store.lookup(b"carol"), Ok(Some(record(5))));
+        assert_eq!(store.lookup(b"bob"), Ok(None));
+
+        #[cfg(unix)] {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        // A truncated file is rejected
+        let data = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &data[..data.len() - 1]).unwrap();
+        assert_eq!(FileCredentialStore::open(&path).err(), Some(StoreError::Storage));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
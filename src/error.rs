@@ -1,5 +1,5 @@
 #[derive(Debug, PartialEq)]
-/// Error types for the failure modes
+/// Error types for the failure modes of the KEM
 pub enum KyberError {
     /// One or more inputs to a function are incorrectly sized. A likely cause of this is two parties using different security
     /// levels while trying to negotiate a key exchange.
@@ -26,47 +26,150 @@ impl core::fmt::Display for KyberError {
     }
 }
 
+#[cfg(any(test, feature = "default-kyber"))]
+impl From<pqc_kyber::KyberError> for KyberError {
+    fn from(e: pqc_kyber::KyberError) -> Self {
+        match e {
+            pqc_kyber::KyberError::InvalidInput => KyberError::InvalidInput,
+            pqc_kyber::KyberError::Decapsulation => KyberError::Decapsulation,
+            pqc_kyber::KyberError::RandomBytesGeneration => KyberError::RandomBytesGeneration,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
+/// Error types for the failure modes of a credential store
+pub enum StoreError {
+    /// No credentials are registered for the account.
+    UnknownAccount,
+    /// Credentials are already registered for the account.
+    AccountExists,
+    /// The password hash could not be computed, e.g. because of invalid cost parameters.
+    PasswordHashing,
+    /// The credential store could not be read or written.
+    Storage,
+}
+
+impl core::fmt::Display for StoreError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            StoreError::UnknownAccount => write!(f, "No credentials registered for the account"),
+            StoreError::AccountExists => write!(f, "Credentials already registered for the account"),
+            StoreError::PasswordHashing => write!(f, "Password hashing failed"),
+            StoreError::Storage => write!(f, "Credential store could not be read or written"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+/// Error types for the failure modes of the PAKE
+///
+/// This is the only error type returned by the protocol API; failures of the KEM and of the
+/// credential store are wrapped and, with the std or core-error feature, available through `source()`.
 pub enum PakeError {
-    InvalidInput,
-    KemError,
-    HicError(HicError),
-    Other
+    /// Key confirmation failed: the peers used different passwords or sids, or the transcript was modified.
+    AuthenticationFailure,
+    /// A protocol message has the wrong length or is not a canonical encoding.
+    MalformedMessage,
+    /// The requested parameter set, backend or message format does not match the one in use.
+    ParameterMismatch,
+    /// The KEM failed.
+    Kem(KyberError),
+    /// Error trying to fill random bytes (i.e external (hardware) RNG modules can fail).
+    RandomBytesGeneration,
+    /// The guess limiter refused the attempt: no guesses left in the current window, or still in backoff.
+    RateLimited,
+    /// The guess limiter locked the account after too many failed key confirmations.
+    AccountLocked,
+    /// The credential store failed.
+    Store(StoreError),
+    /// The resumption ticket has expired.
+    TicketExpired,
+    /// The resumption ticket has already been used.
+    TicketReplayed,
 }
 
 impl core::fmt::Display for PakeError {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
-            PakeError::InvalidInput => write!(f, "Function input is of incorrect length"),
-            PakeError::KemError => {
-                write!(f, "An error ocurred in a KEM function!")
+            PakeError::AuthenticationFailure => {
+                write!(f, "Authentication failed, the peers do not share the same password and transcript")
             }
-            PakeError::HicError(e) => {
-                write!(f, "An error ocurred in a HIC function! Error: {:?}", e)
+            PakeError::MalformedMessage => {
+                write!(f, "Protocol message is of incorrect length or not canonically encoded")
             }
-            PakeError::Other => {
-                write!(f, "An error occurred in a PAKE function")
+            PakeError::ParameterMismatch => {
+                write!(f, "Parameter set or backend does not match")
             }
+            PakeError::Kem(_) => write!(f, "KEM failure"),
+            PakeError::RandomBytesGeneration => {
+                write!(f, "Random bytes generation function failed")
+            }
+            PakeError::RateLimited => write!(f, "Too many password guesses, try again later"),
+            PakeError::AccountLocked => write!(f, "Account locked after too many failed password guesses"),
+            PakeError::Store(_) => write!(f, "Credential store failure"),
+            PakeError::TicketExpired => write!(f, "Resumption ticket has expired"),
+            PakeError::TicketReplayed => write!(f, "Resumption ticket has already been used"),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum HicError {
-    InvalidInput,
-    Other
+impl From<KyberError> for PakeError {
+    fn from(e: KyberError) -> Self {
+        PakeError::Kem(e)
+    }
 }
 
-impl core::fmt::Display for HicError {
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
-        match *self {
-            HicError::InvalidInput => write!(f, "Function input is of incorrect length"),
-            HicError::Other => {
-                write!(f, "An error occurred in a HIC function")
-            }
-        }
+impl From<StoreError> for PakeError {
+    fn from(e: StoreError) -> Self {
+        PakeError::Store(e)
     }
 }
 
+#[cfg(any(test, feature = "default-kyber"))]
+impl From<pqc_kyber::KyberError> for PakeError {
+    fn from(e: pqc_kyber::KyberError) -> Self {
+        PakeError::Kem(e.into())
+    }
+}
+
+// std::error::Error with std, core::error::Error (Rust 1.81 or later) with core-error alone
 #[cfg(feature = "std")]
-impl std::error::Error for KyberError {}
+use std::error::Error;
+#[cfg(all(feature = "core-error", not(feature = "std")))]
+use core::error::Error;
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl Error for KyberError {}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl Error for StoreError {}
+
+#[cfg(any(feature = "std", feature = "core-error"))]
+impl Error for PakeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PakeError::Kem(e) => Some(e),
+            PakeError::Store(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "std", feature = "core-error")))]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_source_chaining() {
+        let e = PakeError::from(KyberError::Decapsulation);
+        assert_eq!(e.source().unwrap().downcast_ref::<KyberError>(), Some(&KyberError::Decapsulation));
+
+        let e = PakeError::from(StoreError::Storage);
+        assert_eq!(e.source().unwrap().downcast_ref::<StoreError>(), Some(&StoreError::Storage));
+
+        assert!(PakeError::AuthenticationFailure.source().is_none());
+        assert!(PakeError::MalformedMessage.source().is_none());
+    }
+}
@@ -0,0 +1,298 @@
+//! Group PAKE for n parties sharing one password, compiled from pairwise CHIC runs.
+//!
+//! The parties P_0..P_{n-1} stand in a ring. P_i runs CHIC as initiator with its right neighbour
+//! P_{i+1} and as responder with its left neighbour P_{i-1} (indices mod n). The run between
+//! P_i and P_{i+1} uses sid_i = H("CHIC-group" || group_sid || i || n), so no run can be replayed
+//! in another position or another group. Once both runs are done, P_i broadcasts
+//! X_i = k_{i-1} xor k_i together with the initiator key confirmation tag of its run with P_{i+1}.
+//! From all X_j and its own k_i, each party recovers every pairwise key k_0..k_{n-1}. The group
+//! key is H("CHIC-group-key" || k_0 || ... || k_{n-1} || T), where T covers the group sid, n,
+//! and every msg1, msg2, X_j and confirmation tag in ring order, so parties that saw different
+//! transcripts end up with different keys.
+//!
+//! A final round confirms the group key: each party broadcasts
+//! H(c || i), where c = H("CHIC-group-cnf" || k_0 || ... || k_{n-1} || T), and only accepts the
+//! group key once the tags of all n parties verify. A party that aborts, or derived another key,
+//! makes every other party fail as well.
+//!
+//! This follows the compiler of Abdalla, Bresson, Chevassut and Pointcheval (PKC 2006). An
+//! eavesdropper learns the xor of neighbouring keys only, never a key itself.
+//!
+//! All messages are assumed to be broadcast: finish needs all msg1, msg2 and broadcasts, and
+//! confirm all confirmation tags.
+
+use rand_core::{CryptoRng, RngCore};
+use crate::api::{pake_init_end, pake_init_start_with_sid, pake_resp, pake_resp_confirm, pake_split_msg1};
+use crate::error::PakeError;
+use crate::params::*;
+use crate::reference::verify::verify;
+use crate::symmetric::hash_h;
+
+/// Size of the broadcast of a party: X_i || confirmation tag
+pub const GROUP_BROADCAST_BYTES: usize = 2 * KYBER_SYMBYTES;
+/// Size of the group key confirmation tag of a party
+pub const GROUP_CONFIRM_BYTES: usize = KYBER_SYMBYTES;
+
+const GROUP_SID_LABEL: &[u8;10] = b"CHIC-group";
+const GROUP_KEY_LABEL: &[u8;14] = b"CHIC-group-key";
+const GROUP_CONFIRM_LABEL: &[u8;14] = b"CHIC-group-cnf";
+
+/// H(confirm_key || index)
+fn group_confirm_tag(confirm_key: &[u8;KYBER_SYMBYTES], index: usize) -> [u8;GROUP_CONFIRM_BYTES] {
+    let mut hashin = [0u8;KYBER_SYMBYTES+4];
+    let mut tag = [0u8;GROUP_CONFIRM_BYTES];
+    hashin[..KYBER_SYMBYTES].copy_from_slice(confirm_key);
+    hashin[KYBER_SYMBYTES..].copy_from_slice(&(index as u32).to_le_bytes());
+    hash_h(&mut tag, &hashin, hashin.len());
+    tag
+}
+
+/// sid of the run between P_index (initiator) and P_{index+1} (responder)
+pub fn group_pair_sid(group_sid: &[u8;KYBER_SYMBYTES], index: usize, n: usize) -> [u8;KYBER_SYMBYTES] {
+    let mut hashin = [0u8;GROUP_SID_LABEL.len()+KYBER_SYMBYTES+8];
+    let mut sid = [0u8;KYBER_SYMBYTES];
+    hashin[..GROUP_SID_LABEL.len()].copy_from_slice(GROUP_SID_LABEL);
+    hashin[GROUP_SID_LABEL.len()..GROUP_SID_LABEL.len()+KYBER_SYMBYTES].copy_from_slice(group_sid);
+    hashin[GROUP_SID_LABEL.len()+KYBER_SYMBYTES..GROUP_SID_LABEL.len()+KYBER_SYMBYTES+4].copy_from_slice(&(index as u32).to_le_bytes());
+    hashin[GROUP_SID_LABEL.len()+KYBER_SYMBYTES+4..].copy_from_slice(&(n as u32).to_le_bytes());
+    hash_h(&mut sid, &hashin, hashin.len());
+    sid
+}
+
+/// State of one party of the group
+pub struct GroupParty {
+    index: usize,
+    n: usize,
+    group_sid: [u8;KYBER_SYMBYTES],
+    pw: [u8;KYBER_SYMBYTES],
+    /// Initiator state of the run with the right neighbour
+    msg1: Option<([u8;MSG1_LEN], [u8;KYBER_PUBLICKEYBYTES], [u8;KYBER_SECRETKEYBYTES])>,
+    /// Key and expected confirmation tag of the run with the left neighbour
+    left: Option<([u8;KYBER_SYMBYTES], [u8;KYBER_SYMBYTES])>,
+    /// Key of the run with the right neighbour
+    right: Option<[u8;KYBER_SYMBYTES]>,
+    /// Group key and confirmation key, before confirmation
+    group: Option<([u8;KYBER_SYMBYTES], [u8;KYBER_SYMBYTES])>,
+}
+
+impl GroupParty {
+    /// Party index of a group of n >= 2 parties. group_sid must be fresh and agreed by all parties.
+    pub fn new(index: usize, n: usize, group_sid: &[u8;KYBER_SYMBYTES], pw: &[u8;KYBER_SYMBYTES]) -> Result<Self, PakeError> {
+        if n < 2 || index >= n || n > u32::MAX as usize {
+            return Err(PakeError::ParameterMismatch);
+        }
+        Ok(GroupParty { index, n, group_sid: *group_sid, pw: *pw, msg1: None, left: None, right: None, group: None })
+    }
+
+    fn left_index(&self) -> usize {
+        (self.index + self.n - 1) % self.n
+    }
+
+    /// Round 1: msg1 for the right neighbour
+    pub fn round1<R,F>(&mut self, rng: &mut R, keypair: F) -> Result<[u8;MSG1_LEN], PakeError>
+    where
+        R: CryptoRng + RngCore,
+        F: FnMut(&mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>
+    {
+        let sid = group_pair_sid(&self.group_sid, self.index, self.n);
+        let (enc_pk, pk, sk) = pake_init_start_with_sid(&self.pw, &sid, rng, keypair)?;
+        let (_, msg1) = pake_split_msg1(&enc_pk);
+        self.msg1 = Some((msg1, pk, sk));
+        Ok(msg1)
+    }
+
+    /// Round 2: msg2 for the left neighbour, in answer to its msg1
+    pub fn round2<R,F>(&mut self, msg1_from_left: &[u8;MSG1_LEN], rng: &mut R, encapsulate: F) -> Result<[u8;MSG2_LEN], PakeError>
+    where
+        R: CryptoRng + RngCore,
+        F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>
+    {
+        let sid = group_pair_sid(&self.group_sid, self.left_index(), self.n);
+        let (msg2, key, init_tag) = pake_resp(&sid, msg1_from_left, &self.pw, rng, encapsulate)?;
+        self.left = Some((key, init_tag));
+        Ok(msg2)
+    }
+
+    /// Round 3: on msg2 from the right neighbour, the broadcast X_i || confirmation tag
+    pub fn round3<F>(&mut self, msg2_from_right: &[u8;MSG2_LEN], decapsulate: F) -> Result<[u8;GROUP_BROADCAST_BYTES], PakeError>
+    where
+        F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
+    {
+        let (msg1, pk, sk) = self.msg1.as_ref().ok_or(PakeError::ParameterMismatch)?;
+        let (left_key, _) = self.left.as_ref().ok_or(PakeError::ParameterMismatch)?;
+        let sid = group_pair_sid(&self.group_sid, self.index, self.n);
+        let (key, init_tag) = pake_init_end(msg2_from_right, &sid, msg1, pk, sk, decapsulate)?;
+
+        let mut broadcast = [0u8;GROUP_BROADCAST_BYTES];
+        for i in 0..KYBER_SYMBYTES {
+            broadcast[i] = left_key[i] ^ key[i];
+        }
+        broadcast[KYBER_SYMBYTES..].copy_from_slice(&init_tag);
+        self.right = Some(key);
+        Ok(broadcast)
+    }
+
+    /// Round 4: derives the group key from every party's messages, indexed by the sender
+    /// (msgs1[j] and msgs2[j] belong to the run between P_j and P_{j+1}), and returns the
+    /// confirmation tag to broadcast. The group key is only released by confirm.
+    pub fn finish(&mut self, msgs1: &[[u8;MSG1_LEN]], msgs2: &[[u8;MSG2_LEN]], broadcasts: &[[u8;GROUP_BROADCAST_BYTES]]) -> Result<[u8;GROUP_CONFIRM_BYTES], PakeError> {
+        let right = self.right.as_ref().ok_or(PakeError::ParameterMismatch)?;
+        let (_, expected_tag) = self.left.as_ref().ok_or(PakeError::ParameterMismatch)?;
+        if msgs1.len() != self.n || msgs2.len() != self.n || broadcasts.len() != self.n {
+            return Err(PakeError::MalformedMessage);
+        }
+
+        // Key confirmation of the left neighbour
+        let left = self.left_index();
+        let mut tag = [0u8;KYBER_SYMBYTES];
+        tag.copy_from_slice(&broadcasts[left][KYBER_SYMBYTES..]);
+        pake_resp_confirm(expected_tag, &tag)?;
+
+        // k_{j+1} = X_{j+1} xor k_j, walking the ring from k_index
+        let mut keys = Vec::with_capacity(self.n);
+        keys.resize(self.n, [0u8;KYBER_SYMBYTES]);
+        keys[self.index] = *right;
+        for step in 1..self.n {
+            let prev = (self.index + step - 1) % self.n;
+            let j = (self.index + step) % self.n;
+            for b in 0..KYBER_SYMBYTES {
+                keys[j][b] = broadcasts[j][b] ^ keys[prev][b];
+            }
+        }
+        // The ring closes only if every X_j is consistent with the keys of this party
+        let mut closing = [0u8;KYBER_SYMBYTES];
+        for b in 0..KYBER_SYMBYTES {
+            closing[b] = broadcasts[self.index][b] ^ keys[left][b];
+        }
+        if verify(&closing, right, KYBER_SYMBYTES) != 0 {
+            return Err(PakeError::AuthenticationFailure);
+        }
+
+        let mut hashin = Vec::with_capacity(GROUP_KEY_LABEL.len() + (self.n + 1) * KYBER_SYMBYTES);
+        hashin.extend_from_slice(GROUP_KEY_LABEL);
+        for key in &keys {
+            hashin.extend_from_slice(key);
+        }
+        let mut transcript = Vec::new();
+        transcript.extend_from_slice(&self.group_sid);
+        transcript.extend_from_slice(&(self.n as u32).to_le_bytes());
+        for j in 0..self.n {
+            transcript.extend_from_slice(&msgs1[j]);
+            transcript.extend_from_slice(&msgs2[j]);
+            transcript.extend_from_slice(&broadcasts[j]);
+        }
+        let mut transcript_hash = [0u8;KYBER_SYMBYTES];
+        hash_h(&mut transcript_hash, &transcript, transcript.len());
+        hashin.extend_from_slice(&transcript_hash);
+
+        let mut group_key = [0u8;KYBER_SYMBYTES];
+        hash_h(&mut group_key, &hashin, hashin.len());
+        hashin[..GROUP_CONFIRM_LABEL.len()].copy_from_slice(GROUP_CONFIRM_LABEL);
+        let mut confirm_key = [0u8;KYBER_SYMBYTES];
+        hash_h(&mut confirm_key, &hashin, hashin.len());
+
+        self.group = Some((group_key, confirm_key));
+        Ok(group_confirm_tag(&confirm_key, self.index))
+    }
+
+    /// Checks the confirmation tags of all parties, indexed by the sender, and returns the
+    /// group key. Fails with PakeError::AuthenticationFailure if any party aborted or derived
+    /// another key.
+    pub fn confirm(&self, tags: &[[u8;GROUP_CONFIRM_BYTES]]) -> Result<[u8;KYBER_SYMBYTES], PakeError> {
+        let (group_key, confirm_key) = self.group.as_ref().ok_or(PakeError::ParameterMismatch)?;
+        if tags.len() != self.n {
+            return Err(PakeError::MalformedMessage);
+        }
+        let mut fail = 0u8;
+        for (j, tag) in tags.iter().enumerate() {
+            fail |= verify(&group_confirm_tag(confirm_key, j), tag, GROUP_CONFIRM_BYTES);
+        }
+        if fail != 0 {
+            return Err(PakeError::AuthenticationFailure);
+        }
+        Ok(*group_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use crate::testing::*;
+    use super::*;
+
+    /// In-memory broadcast network. tamper can modify the broadcasts before they are delivered.
+    /// Returns each party's result.
+    fn simulate<T>(seed: u64, pws: &[[u8;KYBER_SYMBYTES]], tamper: T) -> Vec<Result<[u8;KYBER_SYMBYTES], PakeError>>
+    where
+        T: Fn(&mut Vec<[u8;GROUP_BROADCAST_BYTES]>)
+    {
+        let n = pws.len();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut group_sid = [0u8;KYBER_SYMBYTES];
+        rng.fill_bytes(&mut group_sid);
+
+        let mut parties: Vec<GroupParty> = (0..n).map(|i| GroupParty::new(i, n, &group_sid, &pws[i]).unwrap()).collect();
+        let msgs1: Vec<[u8;MSG1_LEN]> = parties.iter_mut().map(|p| p.round1(&mut rng, keypair_func).unwrap()).collect();
+        let msgs2: Vec<[u8;MSG2_LEN]> = (0..n).map(|j| parties[(j + 1) % n].round2(&msgs1[j], &mut rng, encapsulate_func).unwrap()).collect();
+        let mut broadcasts = Vec::new();
+        for (i, party) in parties.iter_mut().enumerate() {
+            match party.round3(&msgs2[i], decapsulate_func) {
+                Ok(b) => broadcasts.push(b),
+                // A party that failed key confirmation aborts; the others see garbage
+                Err(_) => broadcasts.push([0u8;GROUP_BROADCAST_BYTES]),
+            }
+        }
+        tamper(&mut broadcasts);
+        // A party that fails finish aborts as well and sends no valid confirmation tag
+        let finished: Vec<_> = parties.iter_mut().map(|p| p.finish(&msgs1, &msgs2, &broadcasts)).collect();
+        let tags: Vec<[u8;GROUP_CONFIRM_BYTES]> = finished.iter().map(|r| *r.as_ref().unwrap_or(&[0u8;GROUP_CONFIRM_BYTES])).collect();
+        parties.iter().zip(finished).map(|(p, r)| r.and_then(|_| p.confirm(&tags))).collect()
+    }
+
+    #[test]
+    fn test_group_simulator() {
+        for n in 3..=8 {
+            let pws = vec![[n as u8;KYBER_SYMBYTES]; n];
+            let keys: Vec<_> = simulate(n as u64, &pws, |_| {}).into_iter().map(|k| k.unwrap()).collect();
+            assert!(keys.iter().all(|k| *k == keys[0]));
+
+            // Deterministic in the seed, fresh across seeds
+            let again = simulate(n as u64, &pws, |_| {});
+            assert_eq!(again[0], Ok(keys[0]));
+            let other = simulate(100 + n as u64, &pws, |_| {});
+            assert_ne!(other[0], Ok(keys[0]));
+        }
+    }
+
+    #[test]
+    fn test_group_wrong_password() {
+        let mut pws = vec![[1u8;KYBER_SYMBYTES]; 5];
+        pws[2] = [2u8;KYBER_SYMBYTES];
+        let results = simulate(7, &pws, |_| {});
+        assert!(results.iter().all(|r| r.is_err()));
+    }
+
+    #[test]
+    fn test_group_tampered_broadcast() {
+        let pws = vec![[1u8;KYBER_SYMBYTES]; 4];
+        let results = simulate(8, &pws, |b| b[1][0] ^= 1);
+        assert!(results.iter().all(|r| r.is_err()));
+
+        // A modified confirmation tag is caught by the right neighbour, whose abort the final
+        // confirmation round shows to everyone else
+        let results = simulate(8, &pws, |b| b[1][KYBER_SYMBYTES] ^= 1);
+        assert!(results.iter().all(|r| *r == Err(PakeError::AuthenticationFailure)));
+    }
+
+    #[test]
+    fn test_group_parameters() {
+        let sid = [0u8;KYBER_SYMBYTES];
+        let pw = [0u8;KYBER_SYMBYTES];
+        assert_eq!(GroupParty::new(0, 2, &sid, &pw).unwrap().confirm(&[[0u8;GROUP_CONFIRM_BYTES]; 2]), Err(PakeError::ParameterMismatch));
+        assert!(GroupParty::new(0, 1, &sid, &pw).is_err());
+        assert!(GroupParty::new(3, 3, &sid, &pw).is_err());
+        assert_ne!(group_pair_sid(&sid, 0, 3), group_pair_sid(&sid, 1, 3));
+        assert_ne!(group_pair_sid(&sid, 0, 3), group_pair_sid(&sid, 0, 4));
+    }
+}
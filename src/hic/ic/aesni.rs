@@ -0,0 +1,174 @@
+// Rijndael-256/256 with AES-NI. aesenc and aesdec compute a full AES round on each 128-bit half of
+// the state, except that their ShiftRows stays within the half. Before each round the halves are
+// blended and shuffled so that the per-half ShiftRows gives the row shifts 1, 3, 4 of a 256-bit
+// block (Gueron, Intel white paper "Intel Advanced Encryption Standard (AES) New Instructions
+// Set", 2012). The key schedule uses aesenclast for SubWord instead of table lookups, so no step
+// depends on secret data. Encryption computes the round keys as it goes, which overlaps the
+// key schedule with the rounds.
+
+use core::arch::x86_64::*;
+use core::sync::atomic::{AtomicU8, Ordering};
+use crate::hic::ic::constants::R_CON;
+
+/// Number of rounds with a 256-bit block or key
+const ROUNDS: usize = 14;
+
+/// Result of the feature detection, UNKNOWN until the first call to available
+static AESNI: AtomicU8 = AtomicU8::new(UNKNOWN);
+const UNKNOWN: u8 = 0;
+const ABSENT: u8 = 1;
+const PRESENT: u8 = 2;
+
+/// Whether the CPU supports AES-NI, SSSE3 and SSE4.1, detected once and cached
+#[inline(always)]
+pub fn available() -> bool {
+    if cfg!(all(target_feature = "aes", target_feature = "ssse3", target_feature = "sse4.1")) {
+        return true;
+    }
+    match AESNI.load(Ordering::Relaxed) {
+        UNKNOWN => {
+            let present = detect();
+            AESNI.store(if present { PRESENT } else { ABSENT }, Ordering::Relaxed);
+            present
+        }
+        state => state == PRESENT,
+    }
+}
+
+/// CPUID leaf 1: ECX bit 25 is AES-NI, bit 19 SSE4.1 and bit 9 SSSE3. Does not need std, so it
+/// also works in no_std builds.
+#[allow(unused_unsafe)]
+fn detect() -> bool {
+    // SAFETY: every x86_64 CPU implements cpuid and its leaf 1
+    let ecx = unsafe { __cpuid(1) }.ecx;
+    ecx & (1 << 25) != 0 && ecx & (1 << 19) != 0 && ecx & (1 << 9) != 0
+}
+
+/// Rearranges the two halves of the state so that per-half ShiftRows (or InvShiftRows, with the
+/// decryption masks) gives the 256-bit one. Byte i of a half is taken from the other half where
+/// blend has its top bit set, then the bytes are moved within the half by shuffle.
+#[inline]
+#[target_feature(enable = "aes,ssse3,sse4.1")]
+unsafe fn permute(a: __m128i, b: __m128i, blend: __m128i, shuffle: __m128i) -> (__m128i, __m128i) {
+    (
+        _mm_shuffle_epi8(_mm_blendv_epi8(a, b, blend), shuffle),
+        _mm_shuffle_epi8(_mm_blendv_epi8(b, a, blend), shuffle),
+    )
+}
+
+/// Xors into every 32-bit column of k all the columns before it
+#[inline]
+#[target_feature(enable = "aes,ssse3,sse4.1")]
+unsafe fn prefix_xor(k: __m128i) -> __m128i {
+    let k = _mm_xor_si128(k, _mm_slli_si128::<4>(k));
+    _mm_xor_si128(k, _mm_slli_si128::<8>(k))
+}
+
+/// Computes the round keys of round i from those of round i - 1, k0 and k1. Every column of the
+/// state passed to aesenclast is the same word, so its ShiftRows has no effect and it computes
+/// SubWord(word) ^ round constant in every column.
+#[inline]
+#[target_feature(enable = "aes,ssse3,sse4.1")]
+unsafe fn next_round_keys(k0: __m128i, k1: __m128i, i: usize) -> (__m128i, __m128i) {
+    let rot_word3 = _mm_setr_epi8(13, 14, 15, 12, 13, 14, 15, 12, 13, 14, 15, 12, 13, 14, 15, 12);
+    let word3 = _mm_setr_epi8(12, 13, 14, 15, 12, 13, 14, 15, 12, 13, 14, 15, 12, 13, 14, 15);
+    let t = _mm_aesenclast_si128(_mm_shuffle_epi8(k1, rot_word3), _mm_set1_epi32(R_CON[i-1] as i32));
+    let k2 = _mm_xor_si128(prefix_xor(k0), t);
+    let t = _mm_aesenclast_si128(_mm_shuffle_epi8(k2, word3), _mm_setzero_si128());
+    (k2, _mm_xor_si128(prefix_xor(k1), t))
+}
+
+/// Encrypts block in place under key with Rijndael-256
+///
+/// # Safety
+///
+/// The CPU must support AES-NI, SSSE3 and SSE4.1, see available.
+#[target_feature(enable = "aes,ssse3,sse4.1")]
+pub unsafe fn encrypt(block: &mut [u8;32], key: &[u8;32]) {
+    let blend = _mm_setr_epi8(0, -128, -128, -128, 0, 0, -128, -128, 0, 0, -128, -128, 0, 0, 0, -128);
+    let shuffle = _mm_setr_epi8(0, 1, 6, 7, 4, 5, 10, 11, 8, 9, 14, 15, 12, 13, 2, 3);
+    let mut k0 = _mm_loadu_si128(key.as_ptr() as *const __m128i);
+    let mut k1 = _mm_loadu_si128(key.as_ptr().add(16) as *const __m128i);
+    let mut a = _mm_xor_si128(_mm_loadu_si128(block.as_ptr() as *const __m128i), k0);
+    let mut b = _mm_xor_si128(_mm_loadu_si128(block.as_ptr().add(16) as *const __m128i), k1);
+    // the round keys are computed as they are needed
+    for r in 1..ROUNDS {
+        (k0, k1) = next_round_keys(k0, k1, r);
+        let (ta, tb) = permute(a, b, blend, shuffle);
+        a = _mm_aesenc_si128(ta, k0);
+        b = _mm_aesenc_si128(tb, k1);
+    }
+    (k0, k1) = next_round_keys(k0, k1, ROUNDS);
+    let (ta, tb) = permute(a, b, blend, shuffle);
+    a = _mm_aesenclast_si128(ta, k0);
+    b = _mm_aesenclast_si128(tb, k1);
+    _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, a);
+    _mm_storeu_si128(block.as_mut_ptr().add(16) as *mut __m128i, b);
+}
+
+/// Decrypts block in place under key with Rijndael-256, using the equivalent inverse cipher: the
+/// round keys in reverse order, with InvMixColumns applied to all but the first and the last
+///
+/// # Safety
+///
+/// The CPU must support AES-NI, SSSE3 and SSE4.1, see available.
+#[target_feature(enable = "aes,ssse3,sse4.1")]
+pub unsafe fn decrypt(block: &mut [u8;32], key: &[u8;32]) {
+    let blend = _mm_setr_epi8(0, 0, 0, -128, 0, 0, -128, -128, 0, 0, -128, -128, 0, -128, -128, -128);
+    let shuffle = _mm_setr_epi8(0, 1, 14, 15, 4, 5, 2, 3, 8, 9, 6, 7, 12, 13, 10, 11);
+    let mut rk = [_mm_setzero_si128(); 2 * (ROUNDS + 1)];
+    rk[0] = _mm_loadu_si128(key.as_ptr() as *const __m128i);
+    rk[1] = _mm_loadu_si128(key.as_ptr().add(16) as *const __m128i);
+    for i in 1..=ROUNDS {
+        (rk[2*i], rk[2*i+1]) = next_round_keys(rk[2*i-2], rk[2*i-1], i);
+    }
+    let mut a = _mm_xor_si128(_mm_loadu_si128(block.as_ptr() as *const __m128i), rk[2*ROUNDS]);
+    let mut b = _mm_xor_si128(_mm_loadu_si128(block.as_ptr().add(16) as *const __m128i), rk[2*ROUNDS+1]);
+    for r in (1..ROUNDS).rev() {
+        let (ta, tb) = permute(a, b, blend, shuffle);
+        a = _mm_aesdec_si128(ta, _mm_aesimc_si128(rk[2*r]));
+        b = _mm_aesdec_si128(tb, _mm_aesimc_si128(rk[2*r+1]));
+    }
+    let (ta, tb) = permute(a, b, blend, shuffle);
+    a = _mm_aesdeclast_si128(ta, rk[0]);
+    b = _mm_aesdeclast_si128(tb, rk[1]);
+    _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, a);
+    _mm_storeu_si128(block.as_mut_ptr().add(16) as *mut __m128i, b);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use proptest::prelude::*;
+    use crate::hic::ic::rijndael::{DecryptionKey, EncryptionKey};
+    use super::*;
+
+    proptest! {
+        #[test]
+        #[cfg_attr(
+            not(all(target_feature = "aes", target_feature = "ssse3", target_feature = "sse4.1")),
+            ignore = "needs AES-NI at compile time, run with RUSTFLAGS=\"-C target-feature=+aes,+ssse3,+sse4.1\""
+        )]
+        fn prop_aesni_matches_portable(key in any::<[u8;32]>(), block in any::<[u8;32]>()) {
+            // SAFETY: the test only runs when the target features are enabled, so available() holds
+            assert!(available());
+            let mut expected = block;
+            EncryptionKey::new(&key).encrypt_block(&mut expected);
+            let mut b = block;
+            unsafe { encrypt(&mut b, &key) };
+            prop_assert_eq!(b, expected);
+
+            DecryptionKey::new(&key).decrypt_block(&mut expected);
+            unsafe { decrypt(&mut b, &key) };
+            prop_assert_eq!(b, expected);
+            prop_assert_eq!(b, block);
+
+            // decryption of a block that was not produced by encryption
+            let mut expected = block;
+            DecryptionKey::new(&key).decrypt_block(&mut expected);
+            let mut b = block;
+            unsafe { decrypt(&mut b, &key) };
+            prop_assert_eq!(b, expected);
+        }
+    }
+}
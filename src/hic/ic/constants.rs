@@ -12,7 +12,6 @@ pub const SHIFTS: [[[u8; 2]; 4]; 3] = [
     [[0, 0], [1, 7], [3, 5], [4, 4]],
 ];
 
-pub const VALID: [usize; 3] = [16, 24, 32];
 pub const R_CON: [u8; 30] = [
     1, 2, 4, 8, 16, 32, 64, 128, 27, 54, 108, 216, 171, 77, 154, 47, 94, 188, 99, 198, 151, 53,
     106, 212, 179, 125, 250, 239, 197, 145,
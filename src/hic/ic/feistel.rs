@@ -0,0 +1,136 @@
+// Ideal cipher on 256-bit blocks from a keyed Feistel network with SHAKE256 round functions. It needs
+// no tables, only the Keccak permutation that the hashes already use, and every step is
+// constant time. The round functions are made independent by hashing the key and the round number
+// with each input. Feistel networks with independent random round functions are indifferentiable
+// from a random permutation from 8 rounds on (Dai, Steinberger, CRYPTO 2016), but with 128-bit
+// halves the proven bounds, a high power of the number of queries over 2^128, say nothing about
+// attackers that make more than a few thousand queries, and more rounds do not fix that. The round
+// count is therefore a heuristic margin rather than a proven one: 14 rounds, the count of the first
+// complete proof (Holenstein, Kunzler, Tessaro, STOC 2011), against 5 rounds for the best known
+// attack on indifferentiability.
+
+use crate::hic::ic::IdealCipher256;
+use crate::reference::fips202::{keccak_absorb_once, keccakf1600_statepermute, load64, store64, SHAKE256_RATE};
+
+/// Number of Feistel rounds
+const ROUNDS: u8 = 14;
+/// Size of a half block in bytes
+const HALF_BYTES: usize = 16;
+/// Domain separation label of the round functions
+const FEISTEL_LABEL: &[u8;10] = b"CHIC-ic256";
+
+/// Feistel network over two 128-bit halves, with SHAKE256("CHIC-ic256" || key || round || half)
+/// as round function
+pub struct ShakeFeistel256;
+
+/// Xors F_round(key, input) into out
+#[inline(always)]
+fn round_function(out: &mut [u8;HALF_BYTES], key: &[u8;32], round: u8, input: &[u8;HALF_BYTES]) {
+    let mut hashin = [0u8;FEISTEL_LABEL.len()+32+1+HALF_BYTES];
+    let mut s = [0u64;25];
+    hashin[..FEISTEL_LABEL.len()].copy_from_slice(FEISTEL_LABEL);
+    hashin[FEISTEL_LABEL.len()..FEISTEL_LABEL.len()+32].copy_from_slice(key);
+    hashin[FEISTEL_LABEL.len()+32] = round;
+    hashin[FEISTEL_LABEL.len()+33..].copy_from_slice(input);
+    keccak_absorb_once(&mut s, SHAKE256_RATE, &hashin, hashin.len(), 0x1F);
+    keccakf1600_statepermute(&mut s);
+    for i in 0..HALF_BYTES/8 {
+        let x = load64(&out[8*i..]) ^ s[i];
+        store64(&mut out[8*i..], x);
+    }
+}
+
+impl IdealCipher256 for ShakeFeistel256 {
+    const NAME: &'static str = "shake256-feistel";
+
+    fn encrypt(block: &mut [u8;32], key: &[u8;32]) {
+        let mut l = [0u8;HALF_BYTES];
+        let mut r = [0u8;HALF_BYTES];
+        l.copy_from_slice(&block[..HALF_BYTES]);
+        r.copy_from_slice(&block[HALF_BYTES..]);
+        // (l, r) -> (r, l ^ F_i(r))
+        for i in 0..ROUNDS {
+            round_function(&mut l, key, i, &r);
+            core::mem::swap(&mut l, &mut r);
+        }
+        block[..HALF_BYTES].copy_from_slice(&l);
+        block[HALF_BYTES..].copy_from_slice(&r);
+    }
+
+    fn decrypt(block: &mut [u8;32], key: &[u8;32]) {
+        let mut l = [0u8;HALF_BYTES];
+        let mut r = [0u8;HALF_BYTES];
+        l.copy_from_slice(&block[..HALF_BYTES]);
+        r.copy_from_slice(&block[HALF_BYTES..]);
+        // (l, r) -> (r ^ F_i(l), l)
+        for i in (0..ROUNDS).rev() {
+            round_function(&mut r, key, i, &l);
+            core::mem::swap(&mut l, &mut r);
+        }
+        block[..HALF_BYTES].copy_from_slice(&l);
+        block[HALF_BYTES..].copy_from_slice(&r);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::reference::fips202::shake256;
+    use super::*;
+
+    #[test]
+    fn test_shake_feistel_256() {
+        let key = [0x5au8;32];
+        let mut block = [0u8;32];
+        for (i, b) in block.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+
+        // The round function is the first 16 bytes of SHAKE256("CHIC-ic256" || key || round || half)
+        let mut hashin = [0u8;FEISTEL_LABEL.len()+32+1+HALF_BYTES];
+        hashin[..FEISTEL_LABEL.len()].copy_from_slice(FEISTEL_LABEL);
+        hashin[FEISTEL_LABEL.len()..FEISTEL_LABEL.len()+32].copy_from_slice(&key);
+        hashin[FEISTEL_LABEL.len()+32] = 3;
+        hashin[FEISTEL_LABEL.len()+33..].copy_from_slice(&block[HALF_BYTES..]);
+        let mut expected = [0u8;HALF_BYTES];
+        shake256(&mut expected, HALF_BYTES, &hashin, hashin.len());
+        let mut f = [0u8;HALF_BYTES];
+        round_function(&mut f, &key, 3, block[HALF_BYTES..].try_into().unwrap());
+        assert_eq!(f, expected);
+
+        let mut c = block;
+        ShakeFeistel256::encrypt(&mut c, &key);
+        assert_ne!(c, block);
+        let mut other = block;
+        ShakeFeistel256::encrypt(&mut other, &[0x5bu8;32]);
+        assert_ne!(other, c);
+        ShakeFeistel256::decrypt(&mut c, &key);
+        assert_eq!(c, block);
+    }
+
+    // (key, plaintext, ciphertext), computed with hashlib.shake_256 of Python independently of
+    // the Keccak code of this crate
+    const KATS: [([u8;32], [u8;32], [u8;32]); 2] = [
+        (
+            [0x00; 32],
+            [0x00; 32],
+            [0x5d, 0x6c, 0x34, 0xd2, 0x14, 0x5d, 0x7d, 0x67, 0x4a, 0x91, 0x22, 0x9b, 0x64, 0x04, 0x9f, 0x33, 0x14, 0xc1, 0x01, 0x36, 0xea, 0x92, 0xe1, 0x1a, 0xe9, 0x56, 0x46, 0xd8, 0x40, 0xd0, 0x78, 0xdf],
+        ),
+        (
+            [0x5a; 32],
+            [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f],
+            [0xc4, 0xdc, 0x8c, 0x68, 0x1c, 0xe0, 0x0e, 0x9f, 0x17, 0x4d, 0xd5, 0x80, 0x28, 0x38, 0x33, 0xbd, 0xad, 0xee, 0xfb, 0x3c, 0x56, 0x8b, 0xe1, 0xdb, 0x94, 0x8d, 0xd9, 0xde, 0xc6, 0xfa, 0xd9, 0xc4],
+        ),
+    ];
+
+    #[test]
+    fn test_shake_feistel_256_kat() {
+        for (key, plaintext, ciphertext) in KATS {
+            let mut block = plaintext;
+            ShakeFeistel256::encrypt(&mut block, &key);
+            assert_eq!(block, ciphertext);
+            ShakeFeistel256::decrypt(&mut block, &key);
+            assert_eq!(block, plaintext);
+        }
+    }
+}
@@ -1,17 +1,79 @@
+#[cfg(any(test, not(feature = "shake_ic")))]
 pub mod constants;
+#[cfg(any(test, not(feature = "shake_ic")))]
 pub mod rijndael;
-pub mod errors;
+#[cfg(all(target_arch = "x86_64", any(test, not(feature = "shake_ic"))))]
+pub mod aesni;
+#[cfg(any(test, feature = "shake_ic"))]
+pub mod feistel;
 
+/// Ideal cipher on 256-bit blocks, as used by the HIC to encrypt the seed of the public key
+pub trait IdealCipher256 {
+    /// Name of the cipher, reported as the ideal cipher backend of the build
+    const NAME: &'static str;
+
+    /// Name of the code that computes the cipher on this machine, if it has more than one
+    #[cfg(feature = "bench-internals")]
+    fn implementation() -> &'static str {
+        Self::NAME
+    }
+
+    /// Encrypts block in place under key
+    fn encrypt(block: &mut [u8;32], key: &[u8;32]);
+
+    /// Decrypts block in place under key
+    fn decrypt(block: &mut [u8;32], key: &[u8;32]);
+}
+
+/// Ideal cipher of this build: Rijndael-256 by default, the SHAKE256 Feistel network with the
+/// shake_ic feature
+#[cfg(not(feature = "shake_ic"))]
+type Ic256 = rijndael::Rijndael256;
+#[cfg(feature = "shake_ic")]
+type Ic256 = feistel::ShakeFeistel256;
+
+/// Ideal cipher behind ic256_enc and ic256_dec in this build
+pub const IC256_BACKEND: &str = Ic256::NAME;
+
+/// Implementation behind ic256_enc and ic256_dec on this machine, e.g. rijndael-aesni or
+/// rijndael-table
+#[cfg(feature = "bench-internals")]
+pub fn ic256_implementation() -> &'static str {
+    Ic256::implementation()
+}
+
+/// Encrypts block in place under key with the ideal cipher of this build
 pub fn ic256_enc(block: &mut [u8;32], key: &[u8;32]) {
-    // Initialize cipher
-    let cipher = rijndael::Rijndael::new(key, 32).unwrap();
-    let enc_block = cipher.encrypt(block).unwrap();
-    block[0..32].copy_from_slice(&enc_block);
+    Ic256::encrypt(block, key);
+}
+
+/// Decrypts block in place under key with the ideal cipher of this build
+pub fn ic256_dec(block: &mut [u8;32], key: &[u8;32]) {
+    Ic256::decrypt(block, key);
 }
 
-pub fn ic256_dec(enc_block: &mut [u8;32], key: &[u8;32]) {
-    // Initialize cipher
-    let cipher = rijndael::Rijndael::new(key, 32).unwrap();
-    let block = cipher.decrypt(enc_block).unwrap();
-    enc_block[0..32].copy_from_slice(&block);
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+
+    use proptest::prelude::*;
+    use super::*;
+
+    fn dec_inverts_enc<C: IdealCipher256>(key: &[u8;32], block: &[u8;32]) -> bool {
+        let mut b = *block;
+        C::encrypt(&mut b, key);
+        C::decrypt(&mut b, key);
+        b == *block
+    }
+
+    proptest! {
+        #[test]
+        fn prop_ic256_dec_inverts_enc(key in any::<[u8;32]>(), block in any::<[u8;32]>()) {
+            let mut b = block;
+            ic256_enc(&mut b, &key);
+            ic256_dec(&mut b, &key);
+            prop_assert_eq!(b, block);
+            prop_assert!(dec_inverts_enc::<rijndael::Rijndael256>(&key, &block));
+            prop_assert!(dec_inverts_enc::<feistel::ShakeFeistel256>(&key, &block));
+        }
+    }
+}
@@ -1,253 +1,271 @@
 // This file is based on code from https://github.com/Pure-Peace/simple-rijndael/, licensed under the MIT License.
 // Copyright (c) [2021] Pure-Peace
+//
+// Specialized to the ideal cipher of the HIC, Rijndael with a 256-bit block and a 256-bit key: the
+// block size, key size, number of rounds and row shifts are constants, and each direction expands
+// only the key schedule it uses.
 
-use crate::hic::ic::{constants::*, errors::Errors};
+use crate::hic::ic::constants::*;
+use crate::hic::ic::IdealCipher256;
+#[cfg(target_arch = "x86_64")]
+use crate::hic::ic::aesni;
 
-macro_rules! require {
-    ($condition: expr, $err: expr) => {
-        if !$condition {
-            return Err($err);
-        }
-    };
-}
+/// Block size in bytes
+pub const BLOCK_BYTES: usize = 32;
+/// Key size in bytes
+pub const KEY_BYTES: usize = 32;
+/// Number of rounds with a 256-bit block or key
+const ROUNDS: usize = 14;
+/// Number of 32-bit columns of the block
+const B_C: usize = BLOCK_BYTES / 4;
+/// Number of 32-bit columns of the key
+const K_C: usize = KEY_BYTES / 4;
+/// Number of round key columns
+const ROUND_KEY_COUNT: usize = (ROUNDS + 1) * B_C;
+/// Row shifts of a 256-bit block for encryption
+const ENC_SHIFTS: [usize; 3] = [SHIFTS[2][1][0] as usize, SHIFTS[2][2][0] as usize, SHIFTS[2][3][0] as usize];
+/// Row shifts of a 256-bit block for decryption
+const DEC_SHIFTS: [usize; 3] = [SHIFTS[2][1][1] as usize, SHIFTS[2][2][1] as usize, SHIFTS[2][3][1] as usize];
 
-// macro_rules! impl_getters {
-//     ($($field: ident: $type: ty),+) => {
-//         $(
-//             #[inline(always)]
-//             pub fn $field(&self) -> $type {
-//                 self.$field
-//             }
-//         )*
-//     };
-// }
-
-#[derive(Debug)]
-pub struct Rijndael {
-    block_size: usize,
-    rounds: usize,
-    b_c: usize,
-    s_c: usize,
+/// Rijndael-256/256 as an ideal cipher, expanding the key schedule on every call. On x86_64 CPUs
+/// with AES-NI it runs the constant-time aesni code, elsewhere the portable table-based code below.
+pub struct Rijndael256;
+
+/// Encryption key schedule
+pub struct EncryptionKey {
     k_e: RoundKeys,
+}
+
+/// Decryption key schedule
+pub struct DecryptionKey {
     k_d: RoundKeys,
 }
 
-impl Rijndael {
-    // impl_getters!(
-    //     block_size: usize,
-    //     rounds: usize,
-    //     b_c: usize,
-    //     s_c: usize,
-    //     k_e: RoundKeys,
-    //     k_d: RoundKeys
-    // );
+/// Expands key into the encryption round keys
+#[inline(always)]
+fn expand_key(key: &[u8; KEY_BYTES]) -> RoundKeys {
+    let mut k_e = ROUND_KEYS;
 
-    #[inline(always)]
-    pub fn new(key: &[u8], block_size: usize) -> Result<Self, Errors> {
-        require!(VALID.contains(&block_size), Errors::InvalidBlockSize);
-        require!(VALID.contains(&key.len()), Errors::InvalidKeySize);
-        let rounds = if block_size == 32 || key.len() == 32 {
-            14
-        } else {
-            if block_size == 16 && key.len() == 16 {
-                10
-            } else {
-                12
-            }
-        };
-        let b_c = block_size / 4;
-        let s_c = match b_c {
-            4 => 0,
-            6 => 1,
-            _ => 2,
-        };
-        // encryption round keys
-        let mut k_e = ROUND_KEYS;
-        // decryption round keys
-        let mut k_d = ROUND_KEYS;
-        let round_key_count = (rounds + 1) * b_c;
-        let k_c = key.len() / 4;
-
-        // copy user material bytes into temporary ints
-        let mut tk = EMPTY_BLOCK;
-        for i in 0..k_c {
-            let s = 4 * i;
-            tk[i] = ((key[s] as u32) << 24)
-                | ((key[s + 1] as u32) << 16)
-                | ((key[s + 2] as u32) << 8)
-                | (key[s + 3] as u32);
-        }
+    // copy user material bytes into temporary ints
+    let mut tk = EMPTY_BLOCK;
+    for i in 0..K_C {
+        let s = 4 * i;
+        tk[i] = ((key[s] as u32) << 24)
+            | ((key[s + 1] as u32) << 16)
+            | ((key[s + 2] as u32) << 8)
+            | (key[s + 3] as u32);
+    }
+
+    let mut t = 0;
+    let mut j = 0;
+    while j < K_C && t < ROUND_KEY_COUNT {
+        k_e[t / B_C][t % B_C] = tk[j];
+        j += 1;
+        t += 1;
+    }
+    let mut r_con_pointer = 0;
+    loop {
+        let mut tt = tk[K_C - 1];
+        tk[0] ^= ((S[(tt >> 16) as usize & 0xFF] & 0xFF) as u32) << 24
+            ^ ((S[(tt >> 8) as usize & 0xFF] & 0xFF) as u32) << 16
+            ^ ((S[tt as usize & 0xFF] & 0xFF) as u32) << 8
+            ^ ((S[(tt >> 24) as usize & 0xFF] & 0xFF) as u32)
+            ^ ((R_CON[r_con_pointer] & 0xFF) as u32) << 24;
 
-        let mut t = 0;
-        let mut j = 0;
-        while j < k_c && t < round_key_count {
-            k_e[t / b_c][t % b_c] = tk[j];
-            k_d[rounds - (t / b_c)][t % b_c] = tk[j];
+        r_con_pointer += 1;
+        // a 256-bit key takes an extra S-box step halfway through
+        for i in 1..(K_C / 2) {
+            tk[i] ^= tk[i - 1];
+        }
+        tt = tk[(K_C / 2) - 1];
+        tk[K_C / 2] ^= ((S[tt as usize & 0xFF] & 0xFF) as u32)
+            ^ ((S[(tt >> 8) as usize & 0xFF] & 0xFF) as u32) << 8
+            ^ ((S[(tt >> 16) as usize & 0xFF] & 0xFF) as u32) << 16
+            ^ ((S[(tt >> 24) as usize & 0xFF] & 0xFF) as u32) << 24;
+        for i in ((K_C / 2) + 1)..K_C {
+            tk[i] ^= tk[i - 1];
+        }
+        j = 0;
+        while j < K_C && t < ROUND_KEY_COUNT {
+            k_e[t / B_C][t % B_C] = tk[j];
             j += 1;
             t += 1;
         }
-        let mut r_con_pointer = 0;
-        loop {
-            let mut tt = tk[k_c - 1];
-            tk[0] ^= ((S[(tt >> 16) as usize & 0xFF] & 0xFF) as u32) << 24
-                ^ ((S[(tt >> 8) as usize & 0xFF] & 0xFF) as u32) << 16
-                ^ ((S[tt as usize & 0xFF] & 0xFF) as u32) << 8
-                ^ ((S[(tt >> 24) as usize & 0xFF] & 0xFF) as u32)
-                ^ ((R_CON[r_con_pointer] & 0xFF) as u32) << 24;
-
-            r_con_pointer += 1;
-            if k_c != 8 {
-                for i in 1..k_c {
-                    tk[i] ^= tk[i - 1];
-                }
-            } else {
-                for i in 1..(k_c / 2) {
-                    tk[i] ^= tk[i - 1];
-                }
-                tt = tk[(k_c / 2) - 1];
-                tk[k_c / 2] ^= ((S[tt as usize & 0xFF] & 0xFF) as u32)
-                    ^ ((S[(tt >> 8) as usize & 0xFF] & 0xFF) as u32) << 8
-                    ^ ((S[(tt >> 16) as usize & 0xFF] & 0xFF) as u32) << 16
-                    ^ ((S[(tt >> 24) as usize & 0xFF] & 0xFF) as u32) << 24;
-                for i in ((k_c / 2) + 1)..k_c {
-                    tk[i] ^= tk[i - 1];
-                }
-            }
-            j = 0;
-            while j < k_c && t < round_key_count {
-                k_e[t / b_c][t % b_c] = tk[j];
-                k_d[rounds - (t / b_c)][t % b_c] = tk[j];
-                j += 1;
-                t += 1;
-            }
-            if t >= round_key_count {
-                break;
-            }
-        }
-        for r in 1..rounds {
-            for j in 0..b_c {
-                let tt = k_d[r][j];
-                k_d[r][j] = U1[(tt >> 24) as usize & 0xFF]
-                    ^ U2[(tt >> 16) as usize & 0xFF]
-                    ^ U3[(tt >> 8) as usize & 0xFF]
-                    ^ U4[tt as usize & 0xFF];
-            }
+        if t >= ROUND_KEY_COUNT {
+            break;
         }
-        Ok(Self {
-            block_size,
-            rounds,
-            b_c,
-            s_c,
-            k_e,
-            k_d,
-        })
+    }
+    k_e
+}
+
+/// Big-endian columns of block, xored with the first round key
+#[inline(always)]
+fn load_block(block: &[u8; BLOCK_BYTES], round_key: &[u32; 8]) -> [u32; 8] {
+    let mut t = EMPTY_BLOCK;
+    for i in 0..B_C {
+        let s = 4 * i;
+        t[i] = ((block[s] as u32) << 24
+            | (block[s + 1] as u32) << 16
+            | (block[s + 2] as u32) << 8
+            | (block[s + 3] as u32))
+            ^ round_key[i];
+    }
+    t
+}
+
+impl EncryptionKey {
+    #[inline(always)]
+    pub fn new(key: &[u8; KEY_BYTES]) -> Self {
+        EncryptionKey { k_e: expand_key(key) }
     }
 
+    /// Encrypts block in place
     #[inline(always)]
-    pub fn encrypt(&self, source: &[u8]) -> Result<[u8;32], Errors> {
-        require!(source.len() == self.block_size, Errors::InvalidBlockSize);
-        let s1 = SHIFTS[self.s_c][1][0];
-        let s2 = SHIFTS[self.s_c][2][0];
-        let s3 = SHIFTS[self.s_c][3][0];
+    pub fn encrypt_block(&self, block: &mut [u8; BLOCK_BYTES]) {
+        let [s1, s2, s3] = ENC_SHIFTS;
         let mut a = EMPTY_BLOCK;
-        // temporary work array
-        let mut t = EMPTY_BLOCK;
         // source to ints + key
-        for i in 0..self.b_c {
-            let s = 4 * i;
-            t[i] = ((source[s] as u32) << 24
-                | (source[s + 1] as u32) << 16
-                | (source[s + 2] as u32) << 8
-                | (source[s + 3] as u32))
-                ^ self.k_e[0][i];
-        }
+        let mut t = load_block(block, &self.k_e[0]);
         // apply round transforms
-        for r in 1..self.rounds {
-            for i in 0..self.b_c {
+        for r in 1..ROUNDS {
+            for i in 0..B_C {
                 a[i] = (T1[(t[i] >> 24) as usize & 0xFF]
-                    ^ T2[(t[(i + s1 as usize) % self.b_c] >> 16) as usize & 0xFF]
-                    ^ T3[(t[(i + s2 as usize) % self.b_c] >> 8) as usize & 0xFF]
-                    ^ T4[t[(i + s3 as usize) % self.b_c] as usize & 0xFF])
+                    ^ T2[(t[(i + s1) % B_C] >> 16) as usize & 0xFF]
+                    ^ T3[(t[(i + s2) % B_C] >> 8) as usize & 0xFF]
+                    ^ T4[t[(i + s3) % B_C] as usize & 0xFF])
                     ^ self.k_e[r][i];
             }
             t = a;
         }
         // last round is special
-        let mut result = [0u8; 32];
-        let mut index: usize = 0;
-        for i in 0..self.b_c {
-            let tt = self.k_e[self.rounds][i];
-            push4(
-                &mut result,
-                ((S[(t[i] >> 24) as usize & 0xFF] as u32 ^ (tt >> 24)) & 0xFF) as u8,
-                ((S[(t[(i + s1 as usize) % self.b_c] >> 16) as usize & 0xFF] as u32
-                    ^ (tt >> 16))
-                    & 0xFF) as u8,
-                ((S[(t[(i + s2 as usize) % self.b_c] >> 8) as usize & 0xFF] as u32 ^ (tt >> 8))
-                    & 0xFF) as u8,
-                ((S[t[(i + s3 as usize) % self.b_c] as usize & 0xFF] as u32 ^ tt) & 0xFF) as u8,
-                &index
-            );
-            index += 4;
+        for i in 0..B_C {
+            let tt = self.k_e[ROUNDS][i];
+            block[4 * i] = ((S[(t[i] >> 24) as usize & 0xFF] as u32 ^ (tt >> 24)) & 0xFF) as u8;
+            block[4 * i + 1] = ((S[(t[(i + s1) % B_C] >> 16) as usize & 0xFF] as u32 ^ (tt >> 16)) & 0xFF) as u8;
+            block[4 * i + 2] = ((S[(t[(i + s2) % B_C] >> 8) as usize & 0xFF] as u32 ^ (tt >> 8)) & 0xFF) as u8;
+            block[4 * i + 3] = ((S[t[(i + s3) % B_C] as usize & 0xFF] as u32 ^ tt) & 0xFF) as u8;
         }
-        Ok(result)
     }
+}
 
+impl DecryptionKey {
     #[inline(always)]
-    pub fn decrypt(&self, block_cipher: &[u8]) -> Result<[u8;32], Errors> {
-        require!(
-            block_cipher.len() == self.block_size,
-            Errors::InvalidBlockSize
-        );
-        let s1 = SHIFTS[self.s_c][1][1];
-        let s2 = SHIFTS[self.s_c][2][1];
-        let s3 = SHIFTS[self.s_c][3][1];
-        let mut a = EMPTY_BLOCK;
-        let mut t = EMPTY_BLOCK;
-        for i in 0..self.b_c {
-            let s = 4 * i;
-            t[i] = ((block_cipher[s] as u32) << 24
-                | (block_cipher[s + 1] as u32) << 16
-                | (block_cipher[s + 2] as u32) << 8
-                | (block_cipher[s + 3] as u32))
-                ^ self.k_d[0][i];
+    pub fn new(key: &[u8; KEY_BYTES]) -> Self {
+        // the decryption round keys are the encryption round keys in reverse order, with
+        // InvMixColumns applied to all but the first and the last
+        let mut k_d = expand_key(key);
+        k_d.reverse();
+        for r in 1..ROUNDS {
+            for j in 0..B_C {
+                let tt = k_d[r][j];
+                k_d[r][j] = U1[(tt >> 24) as usize & 0xFF]
+                    ^ U2[(tt >> 16) as usize & 0xFF]
+                    ^ U3[(tt >> 8) as usize & 0xFF]
+                    ^ U4[tt as usize & 0xFF];
+            }
         }
-        for r in 1..self.rounds {
-            for i in 0..self.b_c {
+        DecryptionKey { k_d }
+    }
+
+    /// Decrypts block in place
+    #[inline(always)]
+    pub fn decrypt_block(&self, block: &mut [u8; BLOCK_BYTES]) {
+        let [s1, s2, s3] = DEC_SHIFTS;
+        let mut a = EMPTY_BLOCK;
+        let mut t = load_block(block, &self.k_d[0]);
+        for r in 1..ROUNDS {
+            for i in 0..B_C {
                 a[i] = (T5[(t[i] >> 24) as usize & 0xFF]
-                    ^ T6[(t[(i + s1 as usize) % self.b_c] >> 16) as usize & 0xFF]
-                    ^ T7[(t[(i + s2 as usize) % self.b_c] >> 8) as usize & 0xFF]
-                    ^ T8[t[(i + s3 as usize) % self.b_c] as usize & 0xFF])
+                    ^ T6[(t[(i + s1) % B_C] >> 16) as usize & 0xFF]
+                    ^ T7[(t[(i + s2) % B_C] >> 8) as usize & 0xFF]
+                    ^ T8[t[(i + s3) % B_C] as usize & 0xFF])
                     ^ self.k_d[r][i];
             }
             t = a;
         }
-        let mut result = [0u8;32];
-        let mut index: usize = 0;
-        for i in 0..self.b_c {
-            let tt = self.k_d[self.rounds][i];
-            push4(
-                &mut result,
-                ((SI[(t[i] >> 24) as usize & 0xFF] as u32 ^ (tt >> 24)) & 0xFF) as u8,
-                ((SI[(t[(i + s1 as usize) % self.b_c] >> 16) as usize & 0xFF] as u32
-                    ^ (tt >> 16))
-                    & 0xFF) as u8,
-                ((SI[(t[(i + s2 as usize) % self.b_c] >> 8) as usize & 0xFF] as u32
-                    ^ (tt >> 8))
-                    & 0xFF) as u8,
-                ((SI[t[(i + s3 as usize) % self.b_c] as usize & 0xFF] as u32 ^ tt) & 0xFF)
-                    as u8,
-                &index
-            );
-            index += 4;
+        for i in 0..B_C {
+            let tt = self.k_d[ROUNDS][i];
+            block[4 * i] = ((SI[(t[i] >> 24) as usize & 0xFF] as u32 ^ (tt >> 24)) & 0xFF) as u8;
+            block[4 * i + 1] = ((SI[(t[(i + s1) % B_C] >> 16) as usize & 0xFF] as u32 ^ (tt >> 16)) & 0xFF) as u8;
+            block[4 * i + 2] = ((SI[(t[(i + s2) % B_C] >> 8) as usize & 0xFF] as u32 ^ (tt >> 8)) & 0xFF) as u8;
+            block[4 * i + 3] = ((SI[t[(i + s3) % B_C] as usize & 0xFF] as u32 ^ tt) & 0xFF) as u8;
         }
-        Ok(result)
     }
 }
 
-fn push4(dst: &mut [u8;32], val1: u8, val2: u8, val3: u8, val4: u8, start_i: &usize) {
-    dst[*start_i] = val1;
-    dst[*start_i + 1] = val2;
-    dst[*start_i + 2] = val3;
-    dst[*start_i + 3] = val4;
+impl IdealCipher256 for Rijndael256 {
+    const NAME: &'static str = "rijndael256";
+
+    #[cfg(feature = "bench-internals")]
+    fn implementation() -> &'static str {
+        #[cfg(target_arch = "x86_64")]
+        if aesni::available() {
+            return "rijndael-aesni";
+        }
+        "rijndael-table"
+    }
+
+    fn encrypt(block: &mut [u8;32], key: &[u8;32]) {
+        #[cfg(target_arch = "x86_64")]
+        if aesni::available() {
+            // SAFETY: the CPU supports the instructions aesni is compiled for
+            unsafe { aesni::encrypt(block, key) };
+            return;
+        }
+        EncryptionKey::new(key).encrypt_block(block);
+    }
+
+    fn decrypt(block: &mut [u8;32], key: &[u8;32]) {
+        #[cfg(target_arch = "x86_64")]
+        if aesni::available() {
+            // SAFETY: the CPU supports the instructions aesni is compiled for
+            unsafe { aesni::decrypt(block, key) };
+            return;
+        }
+        DecryptionKey::new(key).decrypt_block(block);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // (key, plaintext, ciphertext) for a 256-bit block and key. The ciphertexts come from a
+    // byte-oriented Rijndael written from the specification and sharing no code or tables with
+    // this file; that implementation reproduces the FIPS 197 Appendix B and C vectors with
+    // 128-bit blocks. The last vector uses the digits-of-pi inputs of the Rijndael proposal.
+    const KATS: [([u8; KEY_BYTES], [u8; BLOCK_BYTES], [u8; BLOCK_BYTES]); 4] = [
+        (
+            [0x00; 32],
+            [0x00; 32],
+            [0xc6, 0x22, 0x7e, 0x77, 0x40, 0xb7, 0xe5, 0x3b, 0x5c, 0xb7, 0x78, 0x65, 0x27, 0x8e, 0xab, 0x07, 0x26, 0xf6, 0x23, 0x66, 0xd9, 0xaa, 0xba, 0xd9, 0x08, 0x93, 0x61, 0x23, 0xa1, 0xfc, 0x8a, 0xf3],
+        ),
+        (
+            [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f],
+            [0x03, 0x14, 0x25, 0x36, 0x47, 0x58, 0x69, 0x7a, 0x8b, 0x9c, 0xad, 0xbe, 0xcf, 0xe0, 0xf1, 0x02, 0x13, 0x24, 0x35, 0x46, 0x57, 0x68, 0x79, 0x8a, 0x9b, 0xac, 0xbd, 0xce, 0xdf, 0xf0, 0x01, 0x12],
+            [0xb2, 0x1c, 0xe1, 0x15, 0xfc, 0xa0, 0x66, 0x7b, 0x6c, 0x10, 0x73, 0x3e, 0x1f, 0x70, 0xbb, 0xf8, 0x78, 0x91, 0x12, 0x6a, 0x52, 0x1f, 0xd7, 0xcb, 0x03, 0x54, 0x9c, 0x0d, 0xff, 0x72, 0x43, 0x8f],
+        ),
+        (
+            [0xff; 32],
+            [0xff, 0xfe, 0xfd, 0xfc, 0xfb, 0xfa, 0xf9, 0xf8, 0xf7, 0xf6, 0xf5, 0xf4, 0xf3, 0xf2, 0xf1, 0xf0, 0xef, 0xee, 0xed, 0xec, 0xeb, 0xea, 0xe9, 0xe8, 0xe7, 0xe6, 0xe5, 0xe4, 0xe3, 0xe2, 0xe1, 0xe0],
+            [0x26, 0xea, 0x07, 0x6b, 0xb1, 0xb7, 0x0e, 0x4e, 0xea, 0xb9, 0xb9, 0x82, 0xc8, 0xd2, 0x11, 0x07, 0x49, 0x29, 0x91, 0xa5, 0xcf, 0xd8, 0xbb, 0x04, 0xf6, 0x6f, 0x56, 0x32, 0x37, 0xc6, 0x19, 0x23],
+        ),
+        (
+            [0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf, 0x4f, 0x3c, 0x76, 0x2e, 0x71, 0x60, 0xf3, 0x8b, 0x4d, 0xa5, 0x6a, 0x78, 0x4d, 0x90, 0x45, 0x19, 0x0c, 0xfe],
+            [0x32, 0x43, 0xf6, 0xa8, 0x88, 0x5a, 0x30, 0x8d, 0x31, 0x31, 0x98, 0xa2, 0xe0, 0x37, 0x07, 0x34, 0x4a, 0x40, 0x93, 0x82, 0x22, 0x99, 0xf3, 0x1d, 0x00, 0x82, 0xef, 0xa9, 0x8e, 0xc4, 0xe6, 0xc8],
+            [0xa4, 0x94, 0x06, 0x11, 0x5d, 0xfb, 0x30, 0xa4, 0x04, 0x18, 0xaa, 0xfa, 0x48, 0x69, 0xb7, 0xc6, 0xa8, 0x86, 0xff, 0x31, 0x60, 0x2a, 0x7d, 0xd1, 0x9c, 0x88, 0x9d, 0xc6, 0x4f, 0x7e, 0x4e, 0x7a],
+        ),
+    ];
+
+    #[test]
+    fn test_rijndael_256_kat() {
+        for (key, plaintext, ciphertext) in KATS {
+            let mut block = plaintext;
+            EncryptionKey::new(&key).encrypt_block(&mut block);
+            assert_eq!(block, ciphertext);
+            DecryptionKey::new(&key).decrypt_block(&mut block);
+            assert_eq!(block, plaintext);
+        }
+    }
 }
@@ -1,11 +1,32 @@
-use crate::{reference::polyvec::{polyvec_add, polyvec_frombytes, polyvec_reduce, polyvec_sub, polyvec_tobytes, Polyvec}, KyberError};
-use crate::{symmetric::hash_h,params::*};
+use crate::{reference::polyvec::{polyvec_add, polyvec_frombytes, polyvec_modulus_check, polyvec_reduce, polyvec_sub, polyvec_tobytes, Polyvec}, PakeError};
+use crate::{symmetric::{hash_h,HashH},params::*};
+use utils::GEN_VECTOR_BUFBYTES;
 
-mod ic;
-mod utils;
+pub(crate) mod ic;
+pub(crate) mod utils;
 pub mod sha512;
 pub mod sha256;
 
+/// Scratch memory of hic_eval_in and hic_inv_in, so that the polynomial vectors do not have to
+/// be on the stack. Its contents are overwritten by every call.
+pub struct HicWorkspace {
+    in_t: Polyvec,
+    mask_t: Polyvec,
+    buf: [u8;GEN_VECTOR_BUFBYTES],
+}
+
+impl HicWorkspace {
+    pub const fn new() -> Self {
+        HicWorkspace { in_t: Polyvec::new(), mask_t: Polyvec::new(), buf: [0u8;GEN_VECTOR_BUFBYTES] }
+    }
+}
+
+impl Default for HicWorkspace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Name:  hic_eval
 ///
 /// Description: Computes the "half-ideal cipher" over a Kyber pk
@@ -15,19 +36,50 @@ pub mod sha256;
 /// Arguments:   - [u8] pk: the input public key (of length KYBER_PUBLICKEYBYTES bytes)
 ///              - [u8] pw: input password (of length KYBER_SYMBYTES bytes)
 ///              - [u8] sid: input sid (of length KYBER_SYMBYTES bytes)
+///
+/// Return values: PakeError::MalformedMessage if the vector part of pk is not canonically encoded
 pub fn hic_eval(
     icc: &mut [u8;KYBER_PUBLICKEYBYTES],
     pk: &[u8;KYBER_PUBLICKEYBYTES],
     pw: &[u8;KYBER_SYMBYTES],
     sid: &[u8;KYBER_SYMBYTES]
-) -> Result<(),KyberError>
+) -> Result<(),PakeError>
+{
+    hic_eval_in(&mut HicWorkspace::new(), icc, pk, pw, sid)
+}
+
+/// Name:  hic_eval_in
+///
+/// Description: hic_eval with caller-provided scratch memory
+/// 
+/// Results:     - [u8] icc: output ciphertext (of length KYBER_PUBLICKEYBYTES bytes)
+///
+/// Arguments:   - HicWorkspace ws: scratch memory
+///              - [u8] pk: the input public key (of length KYBER_PUBLICKEYBYTES bytes)
+///              - [u8] pw: input password (of length KYBER_SYMBYTES bytes)
+///              - [u8] sid: input sid (of length KYBER_SYMBYTES bytes)
+///
+/// Return values: see hic_eval
+pub fn hic_eval_in(
+    ws: &mut HicWorkspace,
+    icc: &mut [u8;KYBER_PUBLICKEYBYTES],
+    pk: &[u8;KYBER_PUBLICKEYBYTES],
+    pw: &[u8;KYBER_SYMBYTES],
+    sid: &[u8;KYBER_SYMBYTES]
+) -> Result<(),PakeError>
 {
     let mut hash_in_lr = [0u8;3*KYBER_SYMBYTES];
-    let mut hash_in_rl = [0u8;2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES];
+    let mut hash_rl = HashH::new();
     let mut in_rho = [0u8;KYBER_SYMBYTES];
     let mut key = [0u8;KYBER_SYMBYTES];
     let mut mask_seed_t = [0u8;KYBER_SYMBYTES];
-    let (mut in_t,mut mask_t) = (Polyvec::new(), Polyvec::new());
+    let HicWorkspace { in_t, mask_t, buf } = ws;
+
+    // FIPS 203 modulus check: a non-canonical t would be reduced below and
+    // hic_inv would then recover a different public key
+    if polyvec_modulus_check(pk) != 0 {
+        return Err(PakeError::MalformedMessage);
+    }
 
     //unpack seed part of pk
     in_rho.copy_from_slice(&pk[KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES..]);
@@ -39,21 +91,21 @@ pub fn hic_eval(
     hash_h(&mut mask_seed_t, &hash_in_lr, 3*KYBER_SYMBYTES);
 
     //unpack vec part of pk
-    polyvec_frombytes(&mut in_t, pk);
+    polyvec_frombytes(in_t, pk);
 
     // H'(mask_seed_t) -> mask_t
-    utils::gen_vector(&mut mask_t, &mask_seed_t);
-    polyvec_add(&mut mask_t, &in_t);
-    polyvec_reduce(&mut mask_t);
+    utils::gen_vector_in(mask_t, &mask_seed_t, buf);
+    polyvec_add(mask_t, in_t);
+    polyvec_reduce(mask_t);
 
     //pack vec part of masked pk for hashing
-    polyvec_tobytes(icc, &mask_t);
+    polyvec_tobytes(icc, mask_t);
 
-    // G(pw,vecpartpk) -> key
-    hash_in_rl[..KYBER_SYMBYTES].copy_from_slice(pw);
-    hash_in_rl[KYBER_SYMBYTES..2*KYBER_SYMBYTES].copy_from_slice(sid);
-    hash_in_rl[2*KYBER_SYMBYTES..].copy_from_slice(&icc[..KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES]);
-    hash_h(&mut key, &hash_in_rl, 2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES);
+    // G(pw,vecpartpk) -> key, streamed so that the vector part is not copied
+    hash_rl.update(pw);
+    hash_rl.update(sid);
+    hash_rl.update(&icc[..KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES]);
+    hash_rl.finalize(&mut key);
 
     ic::ic256_enc(&mut in_rho, &key);
 
@@ -73,25 +125,56 @@ pub fn hic_eval(
 /// Arguments:   - [u8] icc: input ciphertext (of length KYBER_PUBLICKEYBYTES bytes)
 ///              - [u8] pw: input password (of length KYBER_SYMBYTES bytes)
 ///              - [u8] sid: input sid (of length KYBER_SYMBYTES bytes)
+///
+/// Return values: PakeError::MalformedMessage if the vector part of icc is not canonically encoded
 pub fn hic_inv(
     pk: &mut [u8;KYBER_PUBLICKEYBYTES],
     icc: &[u8;KYBER_PUBLICKEYBYTES],
     pw: &[u8;KYBER_SYMBYTES],
     sid: &[u8;KYBER_SYMBYTES]
-) -> Result<(),KyberError>
+) -> Result<(),PakeError>
+{
+    hic_inv_in(&mut HicWorkspace::new(), pk, icc, pw, sid)
+}
+
+/// Name:  hic_inv_in
+///
+/// Description: hic_inv with caller-provided scratch memory
+/// 
+/// Results:     - [u8] pk: the output public key (of length KYBER_PUBLICKEYBYTES bytes)
+///
+/// Arguments:   - HicWorkspace ws: scratch memory
+///              - [u8] icc: input ciphertext (of length KYBER_PUBLICKEYBYTES bytes)
+///              - [u8] pw: input password (of length KYBER_SYMBYTES bytes)
+///              - [u8] sid: input sid (of length KYBER_SYMBYTES bytes)
+///
+/// Return values: see hic_inv
+pub fn hic_inv_in(
+    ws: &mut HicWorkspace,
+    pk: &mut [u8;KYBER_PUBLICKEYBYTES],
+    icc: &[u8;KYBER_PUBLICKEYBYTES],
+    pw: &[u8;KYBER_SYMBYTES],
+    sid: &[u8;KYBER_SYMBYTES]
+) -> Result<(),PakeError>
 {
     let mut hash_in_lr = [0u8;3*KYBER_SYMBYTES];
-    let mut hash_in_rl = [0u8;2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES];
+    let mut hash_rl = HashH::new();
     let mut in_rho = [0u8;KYBER_SYMBYTES];
     let mut key = [0u8;KYBER_SYMBYTES];
     let mut mask_seed_t = [0u8;KYBER_SYMBYTES];
-    let (mut in_t,mut mask_t) = (Polyvec::new(), Polyvec::new());
+    let HicWorkspace { in_t, mask_t, buf } = ws;
+
+    // icc is attacker controlled: refuse non-canonical coefficients instead of
+    // silently reducing them (hic_eval never outputs them)
+    if polyvec_modulus_check(icc) != 0 {
+        return Err(PakeError::MalformedMessage);
+    }
 
     // G(pw,vecpartpk) -> key
-    hash_in_rl[..KYBER_SYMBYTES].copy_from_slice(pw);
-    hash_in_rl[KYBER_SYMBYTES..2*KYBER_SYMBYTES].copy_from_slice(sid);
-    hash_in_rl[2*KYBER_SYMBYTES..].copy_from_slice(&icc[..KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES]);
-    hash_h(&mut key, &hash_in_rl, 2*KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES);
+    hash_rl.update(pw);
+    hash_rl.update(sid);
+    hash_rl.update(&icc[..KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES]);
+    hash_rl.finalize(&mut key);
 
     // unpack and decrypt seed part of icc
     in_rho[0..32].copy_from_slice(&icc[KYBER_INDCPA_PUBLICKEYBYTES-KYBER_SYMBYTES..]);
@@ -104,15 +187,15 @@ pub fn hic_inv(
     hash_h(&mut mask_seed_t, &hash_in_lr, 3*KYBER_SYMBYTES);
 
     //unpack vec part of pk
-    polyvec_frombytes(&mut in_t, icc);
+    polyvec_frombytes(in_t, icc);
 
     // H'(mask_seed_t) -> mask_t
-    utils::gen_vector(&mut mask_t, &mask_seed_t);
-    polyvec_sub(&mut mask_t, &in_t);
-    polyvec_reduce(&mut mask_t);
+    utils::gen_vector_in(mask_t, &mask_seed_t, buf);
+    polyvec_sub(mask_t, in_t);
+    polyvec_reduce(mask_t);
 
     //pack_pk
-    polyvec_tobytes(pk, &mask_t);
+    polyvec_tobytes(pk, mask_t);
     pk[KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES..KYBER_PUBLICKEYBYTES].copy_from_slice(&in_rho[..KYBER_SYMBYTES]);
 
     Ok(())
@@ -122,10 +205,23 @@ pub fn hic_inv(
 #[cfg(test)]
 mod tests {
 
-    use rand::RngCore;
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
     use pqc_kyber;
     use super::*;
 
+    /// Overwrites the j-th 12-bit coefficient of a serialized polyvec
+    fn set_coeff(buf: &mut [u8], j: usize, v: u16) {
+        let pos = 3*(j/2);
+        if j % 2 == 0 {
+            buf[pos] = v as u8;
+            buf[pos+1] = (buf[pos+1] & 0xF0) | (v >> 8) as u8;
+        } else {
+            buf[pos+1] = (buf[pos+1] & 0x0F) | (v << 4) as u8;
+            buf[pos+2] = (v >> 4) as u8;
+        }
+    }
+
     #[test]
     fn test_hic() {
         let mut sid = [0u8;KYBER_SSBYTES];
@@ -148,4 +244,68 @@ mod tests {
         assert_eq!(hic_inv(&mut pk_b, &icc, &pw, &sid), Ok(()));
         assert_eq!(pk_a, pk_b);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_hic_eval_rejects_noncanonical_pk() {
+        let mut sid = [0u8;KYBER_SSBYTES];
+        let mut pw = [0u8;KYBER_SSBYTES];
+        let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
+        let mut icc = [0u8;KYBER_PUBLICKEYBYTES];
+
+        let mut rng = rand::thread_rng();
+
+        rng.fill_bytes(&mut sid);
+        rng.fill_bytes(&mut pw);
+
+        let keypair = pqc_kyber::keypair(&mut rng).unwrap();
+        pk.copy_from_slice(&keypair.public);
+
+        set_coeff(&mut pk, 0, KYBER_Q as u16);
+        assert_eq!(hic_eval(&mut icc, &pk, &pw, &sid), Err(PakeError::MalformedMessage));
+    }
+
+    #[test]
+    fn test_hic_inv_rejects_noncanonical_icc() {
+        let mut sid = [0u8;KYBER_SSBYTES];
+        let mut pw = [0u8;KYBER_SSBYTES];
+        let mut pk_a = [0u8;KYBER_PUBLICKEYBYTES];
+        let mut pk_b = [0u8;KYBER_PUBLICKEYBYTES];
+        let mut icc = [0u8;KYBER_PUBLICKEYBYTES];
+
+        let mut rng = rand::thread_rng();
+
+        rng.fill_bytes(&mut sid);
+        rng.fill_bytes(&mut pw);
+
+        let keypair = pqc_kyber::keypair(&mut rng).unwrap();
+        pk_a.copy_from_slice(&keypair.public);
+        assert_eq!(hic_eval(&mut icc, &pk_a, &pw, &sid), Ok(()));
+
+        // Smallest and largest non-canonical value at the ends of each poly
+        for j in (0..KYBER_K).flat_map(|i| [0, 1, KYBER_N-2, KYBER_N-1].map(|c| i*KYBER_N+c)) {
+            for v in [KYBER_Q as u16, 0xFFF] {
+                let mut bad = icc;
+                set_coeff(&mut bad, j, v);
+                assert_eq!(hic_inv(&mut pk_b, &bad, &pw, &sid), Err(PakeError::MalformedMessage));
+            }
+        }
+
+        // The unmodified ciphertext still decodes
+        assert_eq!(hic_inv(&mut pk_b, &icc, &pw, &sid), Ok(()));
+        assert_eq!(pk_a, pk_b);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn prop_hic_inv_inverts_hic_eval(pw in any::<[u8;KYBER_SYMBYTES]>(), sid in any::<[u8;KYBER_SYMBYTES]>(), seed in any::<u64>()) {
+            let pk = pqc_kyber::keypair(&mut StdRng::seed_from_u64(seed)).unwrap().public;
+            let mut icc = [0u8;KYBER_PUBLICKEYBYTES];
+            let mut pk_b = [0u8;KYBER_PUBLICKEYBYTES];
+            prop_assert_eq!(hic_eval(&mut icc, &pk, &pw, &sid), Ok(()));
+            prop_assert_eq!(hic_inv(&mut pk_b, &icc, &pw, &sid), Ok(()));
+            prop_assert_eq!(pk, pk_b);
+        }
+    }
+}
@@ -33,6 +33,11 @@ fn rej_uniform(r: &mut [i16], len: usize, buf: &[u8], buflen: usize) -> usize {
     ctr
 }
 
+const GEN_MATRIX_NBLOCKS: usize =
+    (12 * KYBER_N / 8 * (1 << 12) / KYBER_Q + XOF_BLOCKBYTES) / XOF_BLOCKBYTES;
+/// Size of the XOF output buffer of gen_vector
+pub const GEN_VECTOR_BUFBYTES: usize = GEN_MATRIX_NBLOCKS*XOF_BLOCKBYTES+2;
+
 /// Name:  gen_vector
 ///
 /// Description: Deterministically generate vector v from a seed. Entries of the vector are polynomials that look uniformly random. Performs rejection sampling on output of a XOF
@@ -40,19 +45,30 @@ fn rej_uniform(r: &mut [i16], len: usize, buf: &[u8], buflen: usize) -> usize {
 /// Results:     - Polyvec icc: output vector v
 ///
 /// Arguments:   - [u8] seed: input seed
+#[cfg(feature = "bench-internals")]
 pub fn gen_vector(v: &mut Polyvec, seed: &[u8]) {
-    const GEN_MATRIX_NBLOCKS: usize =
-        (12 * KYBER_N / 8 * (1 << 12) / KYBER_Q + XOF_BLOCKBYTES) / XOF_BLOCKBYTES;
+    let mut buf = [0u8;GEN_VECTOR_BUFBYTES];
+    gen_vector_in(v, seed, &mut buf);
+}
+
+/// Name:  gen_vector_in
+///
+/// Description: gen_vector with a caller-provided XOF output buffer
+/// 
+/// Results:     - Polyvec icc: output vector v
+///
+/// Arguments:   - [u8] seed: input seed
+///              - [u8] buf: scratch buffer (of length GEN_VECTOR_BUFBYTES)
+pub fn gen_vector_in(v: &mut Polyvec, seed: &[u8], buf: &mut [u8;GEN_VECTOR_BUFBYTES]) {
     let mut ctr: usize;
     let (mut buflen, mut off): (usize,usize);
-    let mut buf = [0u8;GEN_MATRIX_NBLOCKS*XOF_BLOCKBYTES+2];
     let mut state = XofState::new();
 
     for i in 0..KYBER_K {
         xof_absorb(&mut state, seed, i as u8, 0u8); //take row 0
-        xof_squeezeblocks(&mut buf, GEN_MATRIX_NBLOCKS, &mut state);
+        xof_squeezeblocks(buf, GEN_MATRIX_NBLOCKS, &mut state);
         buflen = GEN_MATRIX_NBLOCKS*XOF_BLOCKBYTES;
-        ctr = rej_uniform(&mut v.vec[i].coeffs, KYBER_N, &buf, buflen);
+        ctr = rej_uniform(&mut v.vec[i].coeffs, KYBER_N, buf, buflen);
 
         while ctr < KYBER_N {
             off = buflen % 3;
@@ -61,7 +77,7 @@ pub fn gen_vector(v: &mut Polyvec, seed: &[u8]) {
             }
             xof_squeezeblocks(&mut buf[off..], 1, &mut state);
             buflen = off + XOF_BLOCKBYTES;
-            ctr += rej_uniform(&mut v.vec[i].coeffs[ctr..], KYBER_N - ctr, &buf, buflen);
+            ctr += rej_uniform(&mut v.vec[i].coeffs[ctr..], KYBER_N - ctr, buf, buflen);
         }
     }     
 }
\ No newline at end of file
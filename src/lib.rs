@@ -9,7 +9,47 @@ mod symmetric;
 mod reference;
 pub mod params;
 mod rng;
+mod limiter;
+mod credentials;
+mod login;
+mod password_change;
+mod resumption;
+mod observer;
+mod workspace;
+#[cfg(feature = "std")]
+mod group;
+#[cfg(feature = "ml-kem")]
+pub mod mlkem;
+#[cfg(test)]
+mod testing;
 
 pub use api::*;
 pub use error::*;
 pub use params::*;
+pub use limiter::*;
+pub use credentials::*;
+pub use login::*;
+pub use password_change::*;
+pub use resumption::*;
+pub use observer::*;
+pub use workspace::*;
+#[cfg(feature = "std")]
+pub use group::*;
+pub use rng::ShakeRng;
+pub use hic::{hic_eval, hic_inv};
+pub use hic::ic::{IdealCipher256, IC256_BACKEND};
+#[cfg(not(feature = "shake_ic"))]
+pub use hic::ic::rijndael::Rijndael256;
+#[cfg(feature = "shake_ic")]
+pub use hic::ic::feistel::ShakeFeistel256;
+
+/// Internal primitives, exposed for the benchmarks and fuzz targets only. Not part of the public API.
+#[cfg(feature = "bench-internals")]
+#[doc(hidden)]
+pub mod internals {
+    pub use crate::hic::utils::gen_vector;
+    pub use crate::hic::ic::{ic256_enc, ic256_dec, ic256_implementation, IC256_BACKEND};
+    pub use crate::reference::polyvec::Polyvec;
+    pub use crate::reference::verify::{cmov, verify};
+    pub use crate::symmetric::{hash_g, hash_h, HASH_BACKEND};
+}
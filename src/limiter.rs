@@ -0,0 +1,314 @@
+//! Online guess throttling for the responder.
+//!
+//! Every run of the responder lets an active attacker test one password guess, so the number of
+//! runs per account has to be bounded. The responder consults a GuessLimiter before pake_resp
+//! (pake_resp_limited) and reports the outcome of key confirmation (pake_resp_confirm_limited).
+
+use crate::error::PakeError;
+
+/// Source of time for a GuessLimiter, in milliseconds from an arbitrary but fixed origin
+pub trait Clock {
+    fn now_ms(&self) -> u64;
+}
+
+/// Per-account policy deciding whether the responder may run for an account
+pub trait GuessLimiter {
+    /// Called before the responder runs for account. Returns PakeError::RateLimited or
+    /// PakeError::AccountLocked if the attempt must be refused, otherwise counts the attempt.
+    fn check(&mut self, account: &[u8]) -> Result<(), PakeError>;
+
+    /// Called when the initiator's key confirmation tag did not verify.
+    fn record_failure(&mut self, account: &[u8]);
+
+    /// Called when the initiator's key confirmation tag verified.
+    fn record_success(&mut self, account: &[u8]);
+}
+
+/// Parameters of TokenBucketLimiter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GuessPolicy {
+    /// Number of attempts an account can make in a burst
+    pub capacity: u32,
+    /// Time to regain one attempt
+    pub refill_ms: u64,
+    /// Consecutive failures tolerated before backoff starts
+    pub backoff_after: u32,
+    /// First backoff delay, doubled with every further failure
+    pub backoff_base_ms: u64,
+    /// Upper bound of the backoff delay
+    pub backoff_max_ms: u64,
+    /// Consecutive failures that lock the account
+    pub lockout_after: u32,
+    /// How long a locked account stays locked
+    pub lockout_ms: u64,
+    /// Most accounts tracked at once
+    pub max_accounts: usize,
+}
+
+impl Default for GuessPolicy {
+    fn default() -> Self {
+        GuessPolicy {
+            capacity: 5,
+            refill_ms: 60_000,
+            backoff_after: 3,
+            backoff_base_ms: 1_000,
+            backoff_max_ms: 300_000,
+            lockout_after: 10,
+            lockout_ms: 3_600_000,
+            max_accounts: 100_000,
+        }
+    }
+}
+
+/// Wall clock time since the Unix epoch
+#[cfg(feature = "std")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+struct AccountState {
+    /// Attempts left, in units of 1/refill_ms so that refills are exact
+    tokens: u64,
+    last_refill: u64,
+    failures: u32,
+    /// The last attempt has not been confirmed either way
+    pending: bool,
+    next_attempt: u64,
+}
+
+/// In-memory token bucket per account, with exponential backoff and lockout on consecutive
+/// key confirmation failures.
+///
+/// An attempt that is never confirmed (e.g. an attacker that checks its guess against msg2 and
+/// walks away) is counted as a failure when the next attempt for the account starts.
+///
+/// At most policy.max_accounts accounts are tracked. When a new account does not fit, accounts
+/// whose bucket is full again and that have no failures or pending attempt are dropped, since
+/// their state is the same as that of an account never seen. If none can be dropped, attempts
+/// for new accounts fail with PakeError::RateLimited until some can, so flooding the limiter
+/// with account names never erases the record of an account under attack.
+#[cfg(feature = "std")]
+pub struct TokenBucketLimiter<C: Clock> {
+    policy: GuessPolicy,
+    clock: C,
+    accounts: std::collections::HashMap<std::vec::Vec<u8>, AccountState>,
+}
+
+#[cfg(feature = "std")]
+impl<C: Clock> TokenBucketLimiter<C> {
+    pub fn new(policy: GuessPolicy, clock: C) -> Self {
+        TokenBucketLimiter { policy, clock, accounts: std::collections::HashMap::new() }
+    }
+
+    /// Clears the state of an account, e.g. after an administrator unlocked it
+    pub fn reset(&mut self, account: &[u8]) {
+        self.accounts.remove(account);
+    }
+
+    /// Consecutive failures recorded for an account
+    pub fn failures(&self, account: &[u8]) -> u32 {
+        self.accounts.get(account).map_or(0, |s| s.failures)
+    }
+
+    /// Whether state carries no information, i.e. is that of a new account
+    fn is_idle(policy: &GuessPolicy, state: &AccountState, now: u64) -> bool {
+        let full = policy.capacity as u64 * policy.refill_ms.max(1);
+        !state.pending
+            && state.failures == 0
+            && state.next_attempt <= now
+            && state.tokens.saturating_add(now.saturating_sub(state.last_refill)) >= full
+    }
+
+    fn fail(policy: &GuessPolicy, state: &mut AccountState, now: u64) {
+        state.failures = state.failures.saturating_add(1);
+        if state.failures >= policy.lockout_after {
+            state.next_attempt = now.saturating_add(policy.lockout_ms);
+        } else if state.failures > policy.backoff_after {
+            let shift = (state.failures - policy.backoff_after - 1).min(63);
+            let delay = policy.backoff_base_ms.checked_shl(shift).unwrap_or(u64::MAX).min(policy.backoff_max_ms);
+            state.next_attempt = now.saturating_add(delay);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<C: Clock> GuessLimiter for TokenBucketLimiter<C> {
+    fn check(&mut self, account: &[u8]) -> Result<(), PakeError> {
+        let now = self.clock.now_ms();
+        let policy = self.policy;
+        let full = policy.capacity as u64 * policy.refill_ms.max(1);
+        if !self.accounts.contains_key(account) && self.accounts.len() >= policy.max_accounts {
+            self.accounts.retain(|_, state| !Self::is_idle(&policy, state, now));
+            if self.accounts.len() >= policy.max_accounts {
+                return Err(PakeError::RateLimited);
+            }
+        }
+        let state = self.accounts.entry(account.to_vec()).or_insert(AccountState {
+            tokens: full,
+            last_refill: now,
+            failures: 0,
+            pending: false,
+            next_attempt: 0,
+        });
+
+        if state.pending {
+            state.pending = false;
+            Self::fail(&policy, state, now);
+        }
+
+        state.tokens = state.tokens.saturating_add(now.saturating_sub(state.last_refill)).min(full);
+        state.last_refill = now;
+
+        if now < state.next_attempt {
+            if state.failures >= policy.lockout_after {
+                return Err(PakeError::AccountLocked);
+            }
+            return Err(PakeError::RateLimited);
+        }
+        if state.failures >= policy.lockout_after {
+            // Lockout expired, start over with a clean record
+            state.failures = 0;
+        }
+        if state.tokens < policy.refill_ms.max(1) {
+            return Err(PakeError::RateLimited);
+        }
+        state.tokens -= policy.refill_ms.max(1);
+        state.pending = true;
+        Ok(())
+    }
+
+    fn record_failure(&mut self, account: &[u8]) {
+        let now = self.clock.now_ms();
+        let policy = self.policy;
+        if let Some(state) = self.accounts.get_mut(account) {
+            state.pending = false;
+            Self::fail(&policy, state, now);
+        }
+    }
+
+    fn record_success(&mut self, account: &[u8]) {
+        if let Some(state) = self.accounts.get_mut(account) {
+            state.pending = false;
+            state.failures = 0;
+            state.next_attempt = 0;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+
+    use crate::testing::MockClock;
+    use super::*;
+
+    fn policy() -> GuessPolicy {
+        GuessPolicy {
+            capacity: 3,
+            refill_ms: 1_000,
+            backoff_after: 2,
+            backoff_base_ms: 10_000,
+            backoff_max_ms: 40_000,
+            lockout_after: 6,
+            lockout_ms: 1_000_000,
+            max_accounts: 2,
+        }
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let clock = MockClock::default();
+        let mut limiter = TokenBucketLimiter::new(policy(), clock.clone());
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check(b"alice"), Ok(()));
+            limiter.record_success(b"alice");
+        }
+        assert_eq!(limiter.check(b"alice"), Err(PakeError::RateLimited));
+        // Other accounts have their own bucket
+        assert_eq!(limiter.check(b"bob"), Ok(()));
+
+        clock.advance(1_000);
+        assert_eq!(limiter.check(b"alice"), Ok(()));
+        assert_eq!(limiter.check(b"alice"), Err(PakeError::RateLimited));
+    }
+
+    #[test]
+    fn test_backoff_and_lockout() {
+        let clock = MockClock::default();
+        let mut limiter = TokenBucketLimiter::new(policy(), clock.clone());
+
+        // Failures within the tolerance only cost tokens
+        for _ in 0..2 {
+            assert_eq!(limiter.check(b"alice"), Ok(()));
+            limiter.record_failure(b"alice");
+        }
+        assert_eq!(limiter.check(b"alice"), Ok(()));
+        limiter.record_failure(b"alice");
+
+        // Backoff doubles up to backoff_max_ms
+        for delay in [10_000, 20_000, 40_000] {
+            clock.advance(delay - 1);
+            assert_eq!(limiter.check(b"alice"), Err(PakeError::RateLimited));
+            clock.advance(1);
+            assert_eq!(limiter.check(b"alice"), Ok(()));
+            limiter.record_failure(b"alice");
+        }
+        assert_eq!(limiter.failures(b"alice"), 6);
+        clock.advance(40_000);
+        assert_eq!(limiter.check(b"alice"), Err(PakeError::AccountLocked));
+
+        // The lockout expires, after which the account starts over
+        clock.advance(1_000_000);
+        assert_eq!(limiter.check(b"alice"), Ok(()));
+        limiter.record_success(b"alice");
+        assert_eq!(limiter.failures(b"alice"), 0);
+    }
+
+    #[test]
+    fn test_unconfirmed_attempt_counts_as_failure() {
+        let clock = MockClock::default();
+        let mut limiter = TokenBucketLimiter::new(policy(), clock.clone());
+
+        assert_eq!(limiter.check(b"alice"), Ok(()));
+        assert_eq!(limiter.check(b"alice"), Ok(()));
+        assert_eq!(limiter.failures(b"alice"), 1);
+        limiter.record_success(b"alice");
+        assert_eq!(limiter.failures(b"alice"), 0);
+
+        limiter.reset(b"alice");
+        assert_eq!(limiter.failures(b"alice"), 0);
+    }
+
+    #[test]
+    fn test_account_cap() {
+        let clock = MockClock::default();
+        let mut limiter = TokenBucketLimiter::new(policy(), clock.clone());
+
+        assert_eq!(limiter.check(b"alice"), Ok(()));
+        limiter.record_failure(b"alice");
+        assert_eq!(limiter.check(b"bob"), Ok(()));
+        limiter.record_success(b"bob");
+
+        // Neither bucket is full again, so a third account does not fit
+        assert_eq!(limiter.check(b"carol"), Err(PakeError::RateLimited));
+        assert_eq!(limiter.accounts.len(), 2);
+
+        // Once bob's bucket is full, bob is dropped; alice's failure is kept
+        clock.advance(1_000);
+        assert_eq!(limiter.check(b"carol"), Ok(()));
+        assert_eq!(limiter.accounts.len(), 2);
+        assert!(!limiter.accounts.contains_key(&b"bob"[..]));
+        assert_eq!(limiter.failures(b"alice"), 1);
+    }
+}
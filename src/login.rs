@@ -0,0 +1,414 @@
+//! Optional four-flight login protocol for servers that store hashed passwords.
+//!
+//! 1. client -> server: ClientHello, the username and a client nonce
+//! 2. server -> client: ServerHello, the salt, cost parameters and parameter set of the account
+//!    together with a server nonce
+//! 3. client -> server: msg1, computed with pw = derive_pw(password, salt, ...)
+//! 4. server -> client: msg2
+//!
+//! The sid is pake_derive_sid(client nonce, server nonce), so it is not sent with msg1. The server
+//! can then check the initiator's key confirmation tag with pake_resp_confirm.
+//!
+//! For unknown usernames the server answers with a fake salt derived from a server secret and the
+//! username, so the answer is the same every time and looks like the one of a registered account,
+//! and runs the responder with a fake pw. The login then fails at key confirmation, exactly like a
+//! login with a wrong password.
+
+use rand_core::{CryptoRng, RngCore};
+use crate::api::{pake_derive_sid, pake_init_end_bound, pake_init_start_with_sid_bound, pake_resp_bound, pake_split_msg1};
+use crate::credentials::{derive_pw, CredentialRecord, CredentialStore, HashParams, PasswordHashAlgorithm, SALT_BYTES};
+use crate::error::PakeError;
+use crate::params::*;
+use crate::rng::randombytes;
+use crate::symmetric::{hash_h, HashH};
+
+/// Size of the encoding of a ServerHello
+pub const SERVER_HELLO_BYTES: usize = 2 + 12 + SALT_BYTES + KYBER_SYMBYTES;
+
+/// Domain separation labels of the fake credentials
+const FAKE_SALT_LABEL: &[u8] = b"CHIC-fake-salt";
+const FAKE_PW_LABEL: &[u8] = b"CHIC-fake-pw";
+
+/// First flight: the username and the client nonce
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientHello<'a> {
+    pub username: &'a [u8],
+    pub nonce: [u8;KYBER_SYMBYTES],
+}
+
+impl<'a> ClientHello<'a> {
+    /// Encoding: nonce || username
+    #[cfg(feature = "std")]
+    pub fn to_bytes(&self) -> std::vec::Vec<u8> {
+        let mut out = std::vec::Vec::with_capacity(KYBER_SYMBYTES + self.username.len());
+        out.extend_from_slice(&self.nonce);
+        out.extend_from_slice(self.username);
+        out
+    }
+
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, PakeError> {
+        if bytes.len() < KYBER_SYMBYTES {
+            return Err(PakeError::MalformedMessage);
+        }
+        let mut nonce = [0u8;KYBER_SYMBYTES];
+        nonce.copy_from_slice(&bytes[..KYBER_SYMBYTES]);
+        Ok(ClientHello { username: &bytes[KYBER_SYMBYTES..], nonce })
+    }
+}
+
+/// Second flight: how to derive pw for the account, and the server nonce
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerHello {
+    pub algorithm: PasswordHashAlgorithm,
+    pub param_set: u8,
+    pub params: HashParams,
+    pub salt: [u8;SALT_BYTES],
+    pub nonce: [u8;KYBER_SYMBYTES],
+}
+
+impl ServerHello {
+    /// Encoding: the credential record encoding without pw, followed by the nonce
+    pub fn to_bytes(&self) -> [u8;SERVER_HELLO_BYTES] {
+        let record = CredentialRecord {
+            algorithm: self.algorithm,
+            param_set: self.param_set,
+            params: self.params,
+            salt: self.salt,
+            pw: [0u8;KYBER_SYMBYTES],
+        }.to_bytes();
+        let mut out = [0u8;SERVER_HELLO_BYTES];
+        out[..SERVER_HELLO_BYTES-KYBER_SYMBYTES].copy_from_slice(&record[..SERVER_HELLO_BYTES-KYBER_SYMBYTES]);
+        out[SERVER_HELLO_BYTES-KYBER_SYMBYTES..].copy_from_slice(&self.nonce);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PakeError> {
+        if bytes.len() != SERVER_HELLO_BYTES {
+            return Err(PakeError::MalformedMessage);
+        }
+        let mut record = [0u8;crate::credentials::RECORD_BYTES];
+        record[..SERVER_HELLO_BYTES-KYBER_SYMBYTES].copy_from_slice(&bytes[..SERVER_HELLO_BYTES-KYBER_SYMBYTES]);
+        let record = CredentialRecord::from_bytes(&record)?;
+        let mut nonce = [0u8;KYBER_SYMBYTES];
+        nonce.copy_from_slice(&bytes[SERVER_HELLO_BYTES-KYBER_SYMBYTES..]);
+        Ok(ServerHello {
+            algorithm: record.algorithm,
+            param_set: record.param_set,
+            params: record.params,
+            salt: record.salt,
+            nonce,
+        })
+    }
+}
+
+/// Server settings for unknown usernames. fake_algorithm and fake_params should be the ones new
+/// accounts are registered with, so that fake answers cannot be told apart from real ones.
+#[derive(Clone)]
+pub struct LoginServerConfig {
+    /// Long-term secret of the server, keeps fake salts unpredictable
+    pub fake_secret: [u8;KYBER_SYMBYTES],
+    pub fake_algorithm: PasswordHashAlgorithm,
+    pub fake_params: HashParams,
+}
+
+/// Client policy for the unauthenticated ServerHello. Whoever sits on the network can pick the
+/// password hash and its cost parameters, so the client only hashes with the algorithms and up
+/// to the costs it accepts here.
+#[derive(Debug, Clone)]
+pub struct LoginClientConfig<'a> {
+    /// Password hash algorithms the client accepts
+    pub algorithms: &'a [PasswordHashAlgorithm],
+    /// Largest cost parameters the client accepts for Argon2id
+    pub max_params: HashParams,
+}
+
+impl Default for LoginClientConfig<'static> {
+    /// Argon2id only, with at most 256 MiB of memory, 8 passes and 4 lanes
+    fn default() -> Self {
+        LoginClientConfig {
+            algorithms: &[PasswordHashAlgorithm::Argon2id],
+            max_params: HashParams { m_cost: 256 * 1024, t_cost: 8, p_cost: 4 },
+        }
+    }
+}
+
+impl LoginClientConfig<'_> {
+    /// Fails with PakeError::ParameterMismatch if the server asks for an algorithm or costs
+    /// outside the policy
+    fn check(&self, server_hello: &ServerHello) -> Result<(), PakeError> {
+        if !self.algorithms.contains(&server_hello.algorithm) {
+            return Err(PakeError::ParameterMismatch);
+        }
+        let (params, max) = (&server_hello.params, &self.max_params);
+        if server_hello.algorithm == PasswordHashAlgorithm::Argon2id
+            && (params.m_cost > max.m_cost || params.t_cost > max.t_cost || params.p_cost > max.p_cost)
+        {
+            return Err(PakeError::ParameterMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// State of the client between the second and the fourth flight
+pub struct LoginClientState {
+    sid: [u8;KYBER_SYMBYTES],
+    msg1: [u8;MSG1_LEN],
+    pk: [u8;KYBER_PUBLICKEYBYTES],
+    sk: [u8;KYBER_SECRETKEYBYTES],
+}
+
+/// State of the server between the second and the fourth flight
+pub struct LoginServerState {
+    sid: [u8;KYBER_SYMBYTES],
+    pw: [u8;KYBER_SYMBYTES],
+    known: bool,
+}
+
+impl LoginServerState {
+    /// Whether the username was registered; only for the server's own bookkeeping
+    pub fn is_known(&self) -> bool {
+        self.known
+    }
+}
+
+/// H(label || secret || H(username))
+fn fake_value(label: &[u8], secret: &[u8;KYBER_SYMBYTES], username: &[u8]) -> [u8;KYBER_SYMBYTES] {
+    let mut out = [0u8;KYBER_SYMBYTES];
+    hash_h(&mut out, username, username.len());
+    let mut hash = HashH::new();
+    hash.update(label);
+    hash.update(secret);
+    hash.update(&out);
+    hash.finalize(&mut out);
+    out
+}
+
+/// First flight, run by the client
+pub fn login_client_hello<'a,R>(username: &'a [u8], rng: &mut R) -> Result<ClientHello<'a>, PakeError>
+where
+    R: CryptoRng + RngCore
+{
+    let mut nonce = [0u8;KYBER_SYMBYTES];
+    randombytes(&mut nonce, KYBER_SYMBYTES, rng)?;
+    Ok(ClientHello { username, nonce })
+}
+
+/// Second flight, run by the server: looks up the account and answers with its salt and cost
+/// parameters, or with fake ones if the username is not registered. A record created for another
+/// parameter set is answered with fake ones as well, so this never fails depending on whether
+/// the account exists; such a login fails at key confirmation.
+pub fn login_server_hello<S,R>(store: &S, config: &LoginServerConfig, hello: &ClientHello, rng: &mut R) -> Result<(ServerHello, LoginServerState), PakeError>
+where
+    S: CredentialStore,
+    R: CryptoRng + RngCore
+{
+    let mut nonce = [0u8;KYBER_SYMBYTES];
+    randombytes(&mut nonce, KYBER_SYMBYTES, rng)?;
+
+    let fake = || CredentialRecord {
+        algorithm: config.fake_algorithm,
+        param_set: KYBER_K as u8,
+        params: config.fake_params,
+        salt: fake_value(FAKE_SALT_LABEL, &config.fake_secret, hello.username),
+        pw: fake_value(FAKE_PW_LABEL, &config.fake_secret, hello.username),
+    };
+    let (record, known) = match store.lookup(hello.username)? {
+        Some(record) if record.check_param_set().is_ok() => (record, true),
+        Some(_) => (fake(), true),
+        None => (fake(), false),
+    };
+
+    let server_hello = ServerHello {
+        algorithm: record.algorithm,
+        param_set: record.param_set,
+        params: record.params,
+        salt: record.salt,
+        nonce,
+    };
+    let state = LoginServerState { sid: pake_derive_sid(&hello.nonce, &nonce), pw: record.pw, known };
+    Ok((server_hello, state))
+}
+
+/// Third flight, run by the client: derives pw from the password and computes msg1. Fails with
+/// PakeError::ParameterMismatch before hashing if the ServerHello is outside config.
+pub fn login_client_msg1<R,F>(config: &LoginClientConfig, hello: &ClientHello, server_hello: &ServerHello, password: &[u8], channel_binding: &[u8], rng: &mut R, keypair: F) -> Result<([u8;MSG1_LEN], LoginClientState), PakeError>
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>
+{
+    if server_hello.param_set as usize != KYBER_K {
+        return Err(PakeError::ParameterMismatch);
+    }
+    config.check(server_hello)?;
+    let pw = derive_pw(password, server_hello.algorithm, &server_hello.salt, &server_hello.params)?;
+    let sid = pake_derive_sid(&hello.nonce, &server_hello.nonce);
+    let (enc_pk, pk, sk) = pake_init_start_with_sid_bound(&pw, &sid, channel_binding, rng, keypair)?;
+    let (_, msg1) = pake_split_msg1(&enc_pk);
+    Ok((msg1, LoginClientState { sid, msg1, pk, sk }))
+}
+
+/// Fourth flight, run by the server. Returns msg2, the session key and the key confirmation tag
+/// expected from the client, as pake_resp.
+pub fn login_server_msg2<R,F>(state: &LoginServerState, msg1: &[u8;MSG1_LEN], channel_binding: &[u8], rng: &mut R, encapsulate: F) -> PakeEncapsulated
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>
+{
+    pake_resp_bound(&state.sid, msg1, &state.pw, channel_binding, rng, encapsulate)
+}
+
+/// Run by the client on msg2. Returns the session key and the key confirmation tag, as pake_init_end.
+pub fn login_client_finish<F>(state: &LoginClientState, msg2: &[u8;MSG2_LEN], channel_binding: &[u8], decapsulate: F) -> PakeDecapsulated
+where
+    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>
+{
+    pake_init_end_bound(msg2, &state.sid, &state.msg1, &state.pk, &state.sk, channel_binding, decapsulate)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+
+    use crate::api::pake_resp_confirm;
+    use crate::credentials::MemoryCredentialStore;
+    use crate::testing::*;
+    use super::*;
+
+    fn config() -> LoginServerConfig {
+        LoginServerConfig {
+            fake_secret: [9u8;KYBER_SYMBYTES],
+            fake_algorithm: PasswordHashAlgorithm::Raw,
+            fake_params: HashParams { m_cost: 64, t_cost: 1, p_cost: 1 },
+        }
+    }
+
+    fn client_config() -> LoginClientConfig<'static> {
+        LoginClientConfig {
+            algorithms: &[PasswordHashAlgorithm::Raw, PasswordHashAlgorithm::Argon2id],
+            ..LoginClientConfig::default()
+        }
+    }
+
+    /// Runs the four flights over their encodings, then key confirmation
+    fn login(store: &MemoryCredentialStore, username: &[u8], password: &[u8]) -> Result<[u8;KYBER_SYMBYTES], PakeError> {
+        let mut rng = rand::thread_rng();
+        let config = config();
+
+        let flight1 = login_client_hello(username, &mut rng)?.to_bytes();
+        let client_hello = ClientHello::from_bytes(&flight1)?;
+        let (server_hello, server) = login_server_hello(store, &config, &client_hello, &mut rng)?;
+
+        let flight2 = server_hello.to_bytes();
+        let (msg1, client) = login_client_msg1(&client_config(), &client_hello, &ServerHello::from_bytes(&flight2)?, password, &[], &mut rng, keypair_func)?;
+        let (msg2, key_s, expected_tag) = login_server_msg2(&server, &msg1, &[], &mut rng, encapsulate_func)?;
+        let (key_c, tag) = login_client_finish(&client, &msg2, &[], decapsulate_func)?;
+
+        pake_resp_confirm(&expected_tag, &tag)?;
+        assert_eq!(key_c, key_s);
+        Ok(key_c)
+    }
+
+    #[test]
+    fn test_login() {
+        let mut store = MemoryCredentialStore::new();
+        let password = [3u8;KYBER_SYMBYTES];
+        let record = CredentialRecord::new(&password, PasswordHashAlgorithm::Raw, &[1u8;SALT_BYTES], HashParams::default()).unwrap();
+        store.register(b"alice", record).unwrap();
+
+        assert!(login(&store, b"alice", &password).is_ok());
+        assert_eq!(login(&store, b"alice", &[4u8;KYBER_SYMBYTES]), Err(PakeError::AuthenticationFailure));
+    }
+
+    #[test]
+    #[cfg(feature = "argon2")]
+    fn test_login_argon2id() {
+        let mut store = MemoryCredentialStore::new();
+        let params = HashParams { m_cost: 64, t_cost: 1, p_cost: 1 };
+        let record = CredentialRecord::new(b"correct horse", PasswordHashAlgorithm::Argon2id, &[1u8;SALT_BYTES], params).unwrap();
+        store.register(b"alice", record).unwrap();
+
+        assert!(login(&store, b"alice", b"correct horse").is_ok());
+        assert_eq!(login(&store, b"alice", b"battery staple"), Err(PakeError::AuthenticationFailure));
+    }
+
+    #[test]
+    fn test_unknown_user() {
+        let mut rng = rand::thread_rng();
+        let store = MemoryCredentialStore::new();
+        let config = config();
+
+        // Fails like a wrong password
+        assert_eq!(login(&store, b"mallory", &[3u8;KYBER_SYMBYTES]), Err(PakeError::AuthenticationFailure));
+
+        // The fake salt is stable for a username and differs between usernames
+        let hello = login_client_hello(b"mallory", &mut rng).unwrap();
+        let (first, state) = login_server_hello(&store, &config, &hello, &mut rng).unwrap();
+        let (second, _) = login_server_hello(&store, &config, &hello, &mut rng).unwrap();
+        assert!(!state.is_known());
+        assert_eq!(first.salt, second.salt);
+        assert_eq!((first.algorithm, first.params, first.param_set), (config.fake_algorithm, config.fake_params, KYBER_K as u8));
+        assert_ne!(first.nonce, second.nonce);
+
+        let hello = login_client_hello(b"trudy", &mut rng).unwrap();
+        let (other, _) = login_server_hello(&store, &config, &hello, &mut rng).unwrap();
+        assert_ne!(first.salt, other.salt);
+    }
+
+    #[test]
+    fn test_param_set_mismatch() {
+        let mut rng = rand::thread_rng();
+        let store = MemoryCredentialStore::new();
+        let hello = login_client_hello(b"alice", &mut rng).unwrap();
+        let (mut server_hello, _) = login_server_hello(&store, &config(), &hello, &mut rng).unwrap();
+        server_hello.param_set ^= 1;
+        assert_eq!(login_client_msg1(&client_config(), &hello, &server_hello, &[3u8;KYBER_SYMBYTES], &[], &mut rng, keypair_func).err(), Some(PakeError::ParameterMismatch));
+        assert_eq!(ServerHello::from_bytes(&server_hello.to_bytes()[1..]), Err(PakeError::MalformedMessage));
+    }
+
+    #[test]
+    fn test_client_policy() {
+        let mut rng = rand::thread_rng();
+        let hello = login_client_hello(b"alice", &mut rng).unwrap();
+        let server_hello = ServerHello {
+            algorithm: PasswordHashAlgorithm::Argon2id,
+            param_set: KYBER_K as u8,
+            params: HashParams::default(),
+            salt: [1u8;SALT_BYTES],
+            nonce: [2u8;KYBER_SYMBYTES],
+        };
+        let msg1 = |config: &LoginClientConfig, server_hello: &ServerHello| {
+            login_client_msg1(config, &hello, server_hello, b"correct horse", &[], &mut rand::thread_rng(), keypair_func).err()
+        };
+
+        // Oversized costs are refused before hashing, so this returns at once
+        for params in [
+            HashParams { m_cost: u32::MAX, ..HashParams::default() },
+            HashParams { t_cost: u32::MAX, ..HashParams::default() },
+            HashParams { p_cost: u32::MAX, ..HashParams::default() },
+        ] {
+            let oversized = ServerHello { params, ..server_hello.clone() };
+            assert_eq!(msg1(&LoginClientConfig::default(), &oversized), Some(PakeError::ParameterMismatch));
+        }
+
+        // So is an algorithm the client does not accept
+        let raw = ServerHello { algorithm: PasswordHashAlgorithm::Raw, ..server_hello.clone() };
+        assert_eq!(msg1(&LoginClientConfig::default(), &raw), Some(PakeError::ParameterMismatch));
+        let raw_only = LoginClientConfig { algorithms: &[PasswordHashAlgorithm::Raw], ..LoginClientConfig::default() };
+        assert_eq!(msg1(&raw_only, &server_hello), Some(PakeError::ParameterMismatch));
+    }
+
+    #[test]
+    fn test_stale_record() {
+        let mut rng = rand::thread_rng();
+        let mut store = MemoryCredentialStore::new();
+        let password = [3u8;KYBER_SYMBYTES];
+        let mut record = CredentialRecord::new(&password, PasswordHashAlgorithm::Raw, &[1u8;SALT_BYTES], HashParams::default()).unwrap();
+        record.param_set ^= 1;
+        store.register(b"alice", record).unwrap();
+
+        // Answered like an unknown account, and fails like a wrong password
+        let hello = login_client_hello(b"alice", &mut rng).unwrap();
+        let (server_hello, _) = login_server_hello(&store, &config(), &hello, &mut rng).unwrap();
+        assert_eq!(server_hello.param_set, KYBER_K as u8);
+        assert_eq!(login(&store, b"alice", &password), Err(PakeError::AuthenticationFailure));
+    }
+}
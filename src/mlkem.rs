@@ -0,0 +1,117 @@
+//! FIPS 203 ML-KEM backend (from libcrux).
+//!
+//! ML-KEM keys and ciphertexts have the same sizes as round-3 Kyber, so these functions plug
+//! straight into the keypair, encapsulate and decapsulate closures of the generic API. Key
+//! generation and encapsulation are the deterministic ("derand") entry points; the caller draws
+//! the coins.
+
+use crate::params::*;
+
+#[cfg(feature = "use_kyber512")]
+use libcrux_ml_kem::mlkem512 as mlkem;
+#[cfg(all(feature = "use_kyber1024", not(feature = "use_kyber512")))]
+use libcrux_ml_kem::mlkem1024 as mlkem;
+#[cfg(not(any(feature = "use_kyber512", feature = "use_kyber1024")))]
+use libcrux_ml_kem::mlkem768 as mlkem;
+
+use libcrux_ml_kem::{MlKemCiphertext, MlKemPrivateKey, MlKemPublicKey};
+
+/// Size of the coins consumed by keypair_derand (d || z)
+pub const MLKEM_KEYGEN_SEEDBYTES: usize = 2 * KYBER_SYMBYTES;
+/// Size of the coins consumed by encapsulate_derand (m)
+pub const MLKEM_ENCAPS_SEEDBYTES: usize = KYBER_SYMBYTES;
+
+/// Name:  keypair_derand
+///
+/// Description: ML-KEM.KeyGen_internal
+///
+/// Arguments:   - [u8] coins: the key generation randomness d || z (of length MLKEM_KEYGEN_SEEDBYTES)
+///
+/// Returns the encapsulation key and the decapsulation key
+pub fn keypair_derand(
+    coins: &[u8; MLKEM_KEYGEN_SEEDBYTES],
+) -> ([u8; KYBER_PUBLICKEYBYTES], [u8; KYBER_SECRETKEYBYTES]) {
+    let keys = mlkem::generate_key_pair(*coins);
+    let mut pk = [0u8; KYBER_PUBLICKEYBYTES];
+    let mut sk = [0u8; KYBER_SECRETKEYBYTES];
+    pk.copy_from_slice(keys.public_key().as_slice());
+    sk.copy_from_slice(keys.private_key().as_slice());
+    (pk, sk)
+}
+
+/// Name:  encapsulate_derand
+///
+/// Description: ML-KEM.Encaps_internal
+///
+/// Arguments:   - [u8] pk: the encapsulation key (of length KYBER_PUBLICKEYBYTES)
+///              - [u8] coins: the encapsulation randomness m (of length MLKEM_ENCAPS_SEEDBYTES)
+///
+/// Returns the ciphertext and the shared secret
+pub fn encapsulate_derand(
+    pk: &[u8; KYBER_PUBLICKEYBYTES],
+    coins: &[u8; MLKEM_ENCAPS_SEEDBYTES],
+) -> ([u8; KYBER_CIPHERTEXTBYTES], [u8; KYBER_SSBYTES]) {
+    let pk = MlKemPublicKey::<KYBER_PUBLICKEYBYTES>::from(*pk);
+    let (ct, ss) = mlkem::encapsulate(&pk, *coins);
+    let mut out = [0u8; KYBER_CIPHERTEXTBYTES];
+    out.copy_from_slice(ct.as_slice());
+    (out, ss)
+}
+
+/// Name:  decapsulate
+///
+/// Description: ML-KEM.Decaps; never fails, an invalid ciphertext yields the implicit rejection key
+///
+/// Arguments:   - [u8] ct: the ciphertext (of length KYBER_CIPHERTEXTBYTES)
+///              - [u8] sk: the decapsulation key (of length KYBER_SECRETKEYBYTES)
+///
+/// Returns the shared secret
+pub fn decapsulate(
+    ct: &[u8; KYBER_CIPHERTEXTBYTES],
+    sk: &[u8; KYBER_SECRETKEYBYTES],
+) -> [u8; KYBER_SSBYTES] {
+    let sk = MlKemPrivateKey::<KYBER_SECRETKEYBYTES>::from(*sk);
+    let ct = MlKemCiphertext::<KYBER_CIPHERTEXTBYTES>::from(*ct);
+    mlkem::decapsulate(&sk, &ct)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::RngCore;
+    use crate::api::*;
+    use super::*;
+
+    #[test]
+    fn test_chic_mlkem() {
+        let mut pw = [0u8;KYBER_SYMBYTES];
+        let mut rng = rand::thread_rng();
+        rng.fill_bytes(&mut pw);
+
+        let (enc_pk, pk, sk) = pake_init_start_mlkem(&pw, &mut rng).unwrap();
+        let mut sid = [0u8;KYBER_SYMBYTES];
+        let mut msg1 = [0u8;MSG1_LEN];
+        sid.copy_from_slice(&enc_pk[..KYBER_SYMBYTES]);
+        msg1.copy_from_slice(&enc_pk[KYBER_SYMBYTES..]);
+
+        let (msg2, key_b, tag_b) = pake_resp_mlkem(&sid, &msg1, &pw, &mut rng).unwrap();
+        let (key_a, tag_a) = pake_init_end_mlkem(&msg2, &sid, &msg1, &pk, &sk).unwrap();
+
+        assert_eq!(key_a, key_b);
+        assert_eq!(tag_a, tag_b);
+    }
+
+    #[test]
+    fn test_chic_mlkem_seeded() {
+        let pw = [5u8;KYBER_SYMBYTES];
+        let run = || {
+            let (enc_pk, pk, sk) = pake_init_start_mlkem_seeded(&pw, &[], &[1u8;KYBER_SYMBYTES], &[2u8;KYBER_SYMBYTES]).unwrap();
+            let (sid, msg1) = pake_split_msg1(&enc_pk);
+            let (msg2, key_b, _) = pake_resp_mlkem_seeded(&sid, &msg1, &pw, &[], &[3u8;KYBER_SYMBYTES]).unwrap();
+            let (key_a, _) = pake_init_end_mlkem(&msg2, &sid, &msg1, &pk, &sk).unwrap();
+            assert_eq!(key_a, key_b);
+            (enc_pk, msg2, key_a)
+        };
+        assert_eq!(run(), run());
+    }
+}
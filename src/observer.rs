@@ -0,0 +1,251 @@
+//! Transcript observation for diagnosing failed handshakes.
+//!
+//! The _observed variants of the protocol stages report what each side saw to a
+//! TranscriptObserver: the parameter set, the hash backend and ideal cipher, the sid, the length
+//! and hash of every message, a hash of the whole transcript, and the failure reason. Comparing
+//! the events of both sides tells the causes apart: different parameter sets, hash backends or
+//! ideal ciphers show up directly, a different sid shows up in Start, corrupted messages give
+//! different message hashes, and equal transcript hashes with a failed key confirmation leave the
+//! password or the KEM.
+//!
+//! Events only ever carry public values. Passwords, keys, secret keys, confirmation tags and the
+//! channel binding are never reported, not even hashed.
+
+use rand_core::{CryptoRng, RngCore};
+use crate::api::{pake_init_end_bound, pake_init_start_with_sid_bound, pake_resp_bound};
+use crate::error::PakeError;
+use crate::hic::ic::IC256_BACKEND;
+use crate::params::*;
+use crate::rng::randombytes;
+use crate::symmetric::{hash_h, HASH_BACKEND};
+
+/// Domain separation label of the transcript hash
+const TRACE_LABEL: &[u8;10] = b"CHIC-trace";
+
+/// Protocol stage an event belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceStage {
+    InitStart,
+    Resp,
+    InitEnd,
+}
+
+/// What a stage reports to a TranscriptObserver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TraceEvent<'a> {
+    /// The stage started
+    Start {
+        stage: TraceStage,
+        /// KYBER_K of this build
+        param_set: u8,
+        /// Hash backend of this build
+        hash_backend: &'static str,
+        /// Ideal cipher of this build
+        ic_backend: &'static str,
+        /// The sid, before channel binding
+        sid: [u8;KYBER_SYMBYTES],
+        /// Whether a non-empty channel binding was passed
+        channel_bound: bool,
+    },
+    /// A message was sent or received
+    Message {
+        stage: TraceStage,
+        /// "msg1" or "msg2"
+        name: &'static str,
+        len: usize,
+        /// H(message)
+        hash: [u8;KYBER_SYMBYTES],
+    },
+    /// H("CHIC-trace" || sid || msg1 || msg2), equal on both sides iff they saw the same messages
+    Transcript {
+        stage: TraceStage,
+        hash: [u8;KYBER_SYMBYTES],
+    },
+    /// The stage succeeded
+    Success {
+        stage: TraceStage,
+    },
+    /// The stage failed
+    Failure {
+        stage: TraceStage,
+        reason: &'a PakeError,
+    },
+}
+
+/// Receives the events of the _observed stages, e.g. to log them
+pub trait TranscriptObserver {
+    fn observe(&mut self, event: &TraceEvent);
+}
+
+fn start<O: TranscriptObserver>(observer: &mut O, stage: TraceStage, sid: &[u8;KYBER_SYMBYTES], channel_binding: &[u8]) {
+    observer.observe(&TraceEvent::Start {
+        stage,
+        param_set: KYBER_K as u8,
+        hash_backend: HASH_BACKEND,
+        ic_backend: IC256_BACKEND,
+        sid: *sid,
+        channel_bound: !channel_binding.is_empty(),
+    });
+}
+
+fn message<O: TranscriptObserver>(observer: &mut O, stage: TraceStage, name: &'static str, msg: &[u8]) {
+    let mut hash = [0u8;KYBER_SYMBYTES];
+    hash_h(&mut hash, msg, msg.len());
+    observer.observe(&TraceEvent::Message { stage, name, len: msg.len(), hash });
+}
+
+fn transcript<O: TranscriptObserver>(observer: &mut O, stage: TraceStage, sid: &[u8;KYBER_SYMBYTES], msg1: &[u8;MSG1_LEN], msg2: &[u8;MSG2_LEN]) {
+    let mut hashin = [0u8;TRACE_LABEL.len()+KYBER_SYMBYTES+MSG1_LEN+MSG2_LEN];
+    let mut hash = [0u8;KYBER_SYMBYTES];
+    hashin[..TRACE_LABEL.len()].copy_from_slice(TRACE_LABEL);
+    hashin[TRACE_LABEL.len()..TRACE_LABEL.len()+KYBER_SYMBYTES].copy_from_slice(sid);
+    hashin[TRACE_LABEL.len()+KYBER_SYMBYTES..TRACE_LABEL.len()+KYBER_SYMBYTES+MSG1_LEN].copy_from_slice(msg1);
+    hashin[TRACE_LABEL.len()+KYBER_SYMBYTES+MSG1_LEN..].copy_from_slice(msg2);
+    hash_h(&mut hash, &hashin, hashin.len());
+    observer.observe(&TraceEvent::Transcript { stage, hash });
+}
+
+fn finish<O: TranscriptObserver, T>(observer: &mut O, stage: TraceStage, result: &Result<T, PakeError>) {
+    match result {
+        Ok(_) => observer.observe(&TraceEvent::Success { stage }),
+        Err(reason) => observer.observe(&TraceEvent::Failure { stage, reason }),
+    }
+}
+
+/// pake_init_start, reporting to observer
+pub fn pake_init_start_observed<R,F,O>(pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R, keypair: F, observer: &mut O) -> PakeKeyPair
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError>,
+    O: TranscriptObserver
+{
+    let mut sid = [0u8;KYBER_SYMBYTES];
+    if let Err(reason) = randombytes(&mut sid, KYBER_SYMBYTES, rng) {
+        observer.observe(&TraceEvent::Failure { stage: TraceStage::InitStart, reason: &reason });
+        return Err(reason);
+    }
+    start(observer, TraceStage::InitStart, &sid, channel_binding);
+    let result = pake_init_start_with_sid_bound(pw, &sid, channel_binding, rng, keypair);
+    if let Ok((enc_pk, _, _)) = &result {
+        message(observer, TraceStage::InitStart, "msg1", &enc_pk[KYBER_SYMBYTES..]);
+    }
+    finish(observer, TraceStage::InitStart, &result);
+    result
+}
+
+/// pake_resp, reporting to observer
+#[allow(clippy::too_many_arguments)]
+pub fn pake_resp_observed<R,F,O>(sid: &[u8;KYBER_SYMBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R, encapsulate: F, observer: &mut O) -> PakeEncapsulated
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError>,
+    O: TranscriptObserver
+{
+    start(observer, TraceStage::Resp, sid, channel_binding);
+    message(observer, TraceStage::Resp, "msg1", pk);
+    let result = pake_resp_bound(sid, pk, pw, channel_binding, rng, encapsulate);
+    if let Ok((msg2, _, _)) = &result {
+        message(observer, TraceStage::Resp, "msg2", msg2);
+        transcript(observer, TraceStage::Resp, sid, pk, msg2);
+    }
+    finish(observer, TraceStage::Resp, &result);
+    result
+}
+
+/// pake_init_end, reporting to observer
+#[allow(clippy::too_many_arguments)]
+pub fn pake_init_end_observed<F,O>(ct: &[u8;MSG2_LEN], sid: &[u8;KYBER_SYMBYTES], enc_pk: &[u8;KYBER_PUBLICKEYBYTES], pk: &[u8;KYBER_PUBLICKEYBYTES], sk: &[u8;KYBER_SECRETKEYBYTES], channel_binding: &[u8], decapsulate: F, observer: &mut O) -> PakeDecapsulated
+where
+    F: FnMut(&[u8;KYBER_CIPHERTEXTBYTES],&[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError>,
+    O: TranscriptObserver
+{
+    start(observer, TraceStage::InitEnd, sid, channel_binding);
+    message(observer, TraceStage::InitEnd, "msg2", ct);
+    transcript(observer, TraceStage::InitEnd, sid, enc_pk, ct);
+    let result = pake_init_end_bound(ct, sid, enc_pk, pk, sk, channel_binding, decapsulate);
+    finish(observer, TraceStage::InitEnd, &result);
+    result
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+
+    use rand::RngCore;
+    use crate::testing::*;
+    use super::*;
+
+    /// Keeps the Debug output of every event, as a logging observer would
+    #[derive(Default)]
+    struct Recorder {
+        lines: Vec<String>,
+        transcripts: Vec<[u8;KYBER_SYMBYTES]>,
+    }
+
+    impl TranscriptObserver for Recorder {
+        fn observe(&mut self, event: &TraceEvent) {
+            if let TraceEvent::Transcript { hash, .. } = event {
+                self.transcripts.push(*hash);
+            }
+            self.lines.push(format!("{:?}", event));
+        }
+    }
+
+    /// Debug rendering of 8 bytes of a secret, as it would appear inside a logged array
+    fn needle(secret: &[u8]) -> String {
+        let s = format!("{:?}", &secret[..8]);
+        String::from(&s[1..s.len()-1])
+    }
+
+    #[test]
+    fn test_trace_is_redacted() {
+        let mut rng = rand::thread_rng();
+        let mut pw = [0u8;KYBER_SYMBYTES];
+        rng.fill_bytes(&mut pw);
+        let cb = b"tls-exporter value of the channel";
+        let (mut initiator, mut responder) = (Recorder::default(), Recorder::default());
+
+        let (enc_pk, pk, sk) = pake_init_start_observed(&pw, cb, &mut rng, keypair_func, &mut initiator).unwrap();
+        let mut sid = [0u8;KYBER_SYMBYTES];
+        let mut msg1 = [0u8;MSG1_LEN];
+        sid.copy_from_slice(&enc_pk[..KYBER_SYMBYTES]);
+        msg1.copy_from_slice(&enc_pk[KYBER_SYMBYTES..]);
+        let (msg2, key_b, tag_b) = pake_resp_observed(&sid, &msg1, &pw, cb, &mut rng, encapsulate_func, &mut responder).unwrap();
+        let (key_a, tag_a) = pake_init_end_observed(&msg2, &sid, &msg1, &pk, &sk, cb, decapsulate_func, &mut initiator).unwrap();
+        assert_eq!(key_a, key_b);
+        assert_eq!(tag_a, tag_b);
+        assert_eq!(initiator.transcripts, responder.transcripts);
+
+        let log = [initiator.lines, responder.lines].concat().join("\n");
+        for secret in [&pw[..], &key_a[..], &tag_a[..], &sk[..], &sk[KYBER_SECRETKEYBYTES-KYBER_SYMBYTES..], &cb[..]] {
+            assert!(!log.contains(&needle(secret)));
+        }
+        // The public values are there
+        assert!(log.contains(&needle(&sid)));
+        assert!(log.contains("Success { stage: InitEnd }"));
+    }
+
+    #[test]
+    fn test_trace_tells_password_from_transport() {
+        let mut rng = rand::thread_rng();
+        let (mut initiator, mut responder) = (Recorder::default(), Recorder::default());
+
+        // Wrong password: same transcript on both sides, key confirmation fails
+        let (enc_pk, pk, sk) = pake_init_start_observed(&[1u8;KYBER_SYMBYTES], &[], &mut rng, keypair_func, &mut initiator).unwrap();
+        let mut sid = [0u8;KYBER_SYMBYTES];
+        let mut msg1 = [0u8;MSG1_LEN];
+        sid.copy_from_slice(&enc_pk[..KYBER_SYMBYTES]);
+        msg1.copy_from_slice(&enc_pk[KYBER_SYMBYTES..]);
+        let (msg2, _, _) = pake_resp_observed(&sid, &msg1, &[2u8;KYBER_SYMBYTES], &[], &mut rng, encapsulate_func, &mut responder).unwrap();
+        let result = pake_init_end_observed(&msg2, &sid, &msg1, &pk, &sk, &[], decapsulate_func, &mut initiator);
+        assert_eq!(result, Err(PakeError::AuthenticationFailure));
+        assert_eq!(initiator.transcripts, responder.transcripts);
+        assert!(initiator.lines.last().unwrap().contains("Failure { stage: InitEnd, reason: AuthenticationFailure }"));
+
+        // Corrupted msg2: the transcripts differ
+        let mut corrupted = msg2;
+        corrupted[MSG2_LEN-1] ^= 1;
+        let mut other = Recorder::default();
+        let _ = pake_init_end_observed(&corrupted, &sid, &msg1, &pk, &sk, &[], decapsulate_func, &mut other);
+        assert_ne!(other.transcripts, responder.transcripts);
+    }
+}
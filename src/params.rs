@@ -2,7 +2,8 @@ use crate::error::PakeError;
 
 pub type PakeKeyPair = Result<([u8; KYBER_SYMBYTES+KYBER_PUBLICKEYBYTES],[u8; KYBER_PUBLICKEYBYTES],[u8; KYBER_SECRETKEYBYTES]), PakeError>;
 pub type PakeEncapsulated = Result<([u8; KYBER_CIPHERTEXTBYTES+KYBER_SYMBYTES], [u8; KYBER_SSBYTES], [u8; KYBER_SYMBYTES]), PakeError>;
-pub type PakeDecapsulated = Result<([u8;KYBER_SYMBYTES], [u8;KYBER_SYMBYTES], u8), PakeError>;
+/// The session key and the initiator's key confirmation tag
+pub type PakeDecapsulated = Result<([u8;KYBER_SYMBYTES], [u8;KYBER_SYMBYTES]), PakeError>;
 
 /// The security level of Kyber
 ///
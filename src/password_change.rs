@@ -0,0 +1,164 @@
+//! Password change over an authenticated session.
+//!
+//! After a CHIC session whose key confirmation succeeded, the client builds the credential record
+//! of its new password and sends it encrypted and authenticated under keys exported from the
+//! session key. The server checks the request and replaces the account's record in one
+//! CredentialStore::change_password call.
+//!
+//! derive_pw runs on the client, but the record carries pw, which is password-equivalent: anyone
+//! who learns it can log in as the user, and for PasswordHashAlgorithm::Raw it is the password
+//! itself. The server learns it, as it must to run the responder, and on the wire it is only
+//! protected by the encryption under the session key.
+//!
+//! Every request is encrypted under a fresh nonce, so a session key can safely protect more than
+//! one request, but the server should still accept at most one password change per session.
+
+use rand_core::{CryptoRng, RngCore};
+use crate::api::pake_export_key;
+use crate::credentials::{CredentialRecord, CredentialStore, RECORD_BYTES};
+use crate::error::PakeError;
+use crate::params::KYBER_SYMBYTES;
+use crate::reference::fips202::shake256;
+use crate::reference::verify::verify;
+use crate::rng::randombytes;
+
+/// Size of a password change request: nonce || encrypted record || tag
+pub const PASSWORD_CHANGE_BYTES: usize = KYBER_SYMBYTES + RECORD_BYTES + KYBER_SYMBYTES;
+
+const ENC_LABEL: &[u8] = b"password change encryption";
+const MAC_LABEL: &[u8] = b"password change authentication";
+
+/// Keystream length, shake256 squeezes whole 64-bit lanes
+const STREAM_BYTES: usize = RECORD_BYTES.div_ceil(8) * 8;
+
+/// XORs the SHAKE256(enc_key || nonce) keystream into data
+fn apply_keystream(data: &mut [u8;RECORD_BYTES], enc_key: &[u8;KYBER_SYMBYTES], nonce: &[u8;KYBER_SYMBYTES]) {
+    let mut hashin = [0u8;2*KYBER_SYMBYTES];
+    hashin[..KYBER_SYMBYTES].copy_from_slice(enc_key);
+    hashin[KYBER_SYMBYTES..].copy_from_slice(nonce);
+    let mut stream = [0u8;STREAM_BYTES];
+    shake256(&mut stream, STREAM_BYTES, &hashin, hashin.len());
+    for (d, s) in data.iter_mut().zip(stream.iter()) {
+        *d ^= s;
+    }
+}
+
+/// tag = SHAKE256(mac_key || nonce || ct)
+fn mac(tag: &mut [u8;KYBER_SYMBYTES], mac_key: &[u8;KYBER_SYMBYTES], nonce_ct: &[u8]) {
+    let mut hashin = [0u8;2*KYBER_SYMBYTES+RECORD_BYTES];
+    hashin[..KYBER_SYMBYTES].copy_from_slice(mac_key);
+    hashin[KYBER_SYMBYTES..].copy_from_slice(nonce_ct);
+    shake256(tag, KYBER_SYMBYTES, &hashin, hashin.len());
+}
+
+/// Name:  password_change_request
+///
+/// Description: Run by the client after a successful session. Encrypts the new credential
+///  record under the session key and a fresh nonce.
+///
+/// Arguments:   - [u8] key: the session key (of length KYBER_SYMBYTES)
+///              - new_record: the record of the new password, see CredentialRecord::new
+///              - rng: random number generator for the nonce
+///
+/// Returns the request (of length PASSWORD_CHANGE_BYTES)
+pub fn password_change_request<R>(key: &[u8;KYBER_SYMBYTES], new_record: &CredentialRecord, rng: &mut R) -> Result<[u8;PASSWORD_CHANGE_BYTES], PakeError>
+where
+    R: CryptoRng + RngCore
+{
+    new_record.check_param_set()?;
+    let enc_key = pake_export_key(key, ENC_LABEL)?;
+    let mac_key = pake_export_key(key, MAC_LABEL)?;
+
+    let mut nonce = [0u8;KYBER_SYMBYTES];
+    randombytes(&mut nonce, KYBER_SYMBYTES, rng)?;
+    let mut ct = new_record.to_bytes();
+    apply_keystream(&mut ct, &enc_key, &nonce);
+
+    let mut request = [0u8;PASSWORD_CHANGE_BYTES];
+    request[..KYBER_SYMBYTES].copy_from_slice(&nonce);
+    request[KYBER_SYMBYTES..KYBER_SYMBYTES+RECORD_BYTES].copy_from_slice(&ct);
+    let mut tag = [0u8;KYBER_SYMBYTES];
+    mac(&mut tag, &mac_key, &request[..KYBER_SYMBYTES+RECORD_BYTES]);
+    request[KYBER_SYMBYTES+RECORD_BYTES..].copy_from_slice(&tag);
+    Ok(request)
+}
+
+/// Name:  password_change_apply
+///
+/// Description: Run by the server, only for a session of username whose key confirmation
+///  succeeded (pake_resp_confirm). Authenticates and decrypts the request and replaces the
+///  record of the account.
+///
+/// Arguments:   - store: the credential store
+///              - [u8] username: the account the session was run for
+///              - [u8] key: the session key (of length KYBER_SYMBYTES)
+///              - [u8] request: the client's request (of length PASSWORD_CHANGE_BYTES)
+///
+/// Return values: PakeError::AuthenticationFailure if the request was not made with this
+///                session key, PakeError::ParameterMismatch if the new record is for another
+///                parameter set, and PakeError::Store for the errors of CredentialStore::change_password
+pub fn password_change_apply<S>(store: &mut S, username: &[u8], key: &[u8;KYBER_SYMBYTES], request: &[u8;PASSWORD_CHANGE_BYTES]) -> Result<(), PakeError>
+where
+    S: CredentialStore
+{
+    let enc_key = pake_export_key(key, ENC_LABEL)?;
+    let mac_key = pake_export_key(key, MAC_LABEL)?;
+
+    let mut tag = [0u8;KYBER_SYMBYTES];
+    mac(&mut tag, &mac_key, &request[..KYBER_SYMBYTES+RECORD_BYTES]);
+    if verify(&tag, &request[KYBER_SYMBYTES+RECORD_BYTES..], KYBER_SYMBYTES) != 0 {
+        return Err(PakeError::AuthenticationFailure);
+    }
+
+    let mut nonce = [0u8;KYBER_SYMBYTES];
+    nonce.copy_from_slice(&request[..KYBER_SYMBYTES]);
+    let mut ct = [0u8;RECORD_BYTES];
+    ct.copy_from_slice(&request[KYBER_SYMBYTES..KYBER_SYMBYTES+RECORD_BYTES]);
+    apply_keystream(&mut ct, &enc_key, &nonce);
+    let new_record = CredentialRecord::from_bytes(&ct)?;
+    new_record.check_param_set()?;
+    Ok(store.change_password(username, new_record)?)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+
+    use crate::credentials::{HashParams, MemoryCredentialStore, PasswordHashAlgorithm, SALT_BYTES};
+    use super::*;
+
+    fn record(byte: u8) -> CredentialRecord {
+        CredentialRecord::new(&[byte;KYBER_SYMBYTES], PasswordHashAlgorithm::Raw, &[byte;SALT_BYTES], HashParams::default()).unwrap()
+    }
+
+    #[test]
+    fn test_fresh_nonce() {
+        let mut rng = rand::thread_rng();
+        let key = [5u8;KYBER_SYMBYTES];
+        let first = password_change_request(&key, &record(1), &mut rng).unwrap();
+        let second = password_change_request(&key, &record(2), &mut rng).unwrap();
+
+        // Two requests under one session key never share a keystream: XORing the ciphertexts
+        // does not give the XOR of the records
+        assert_ne!(first[..KYBER_SYMBYTES], second[..KYBER_SYMBYTES]);
+        let (a, b) = (record(1).to_bytes(), record(2).to_bytes());
+        let leaked = (0..RECORD_BYTES).all(|i| first[KYBER_SYMBYTES+i] ^ second[KYBER_SYMBYTES+i] == a[i] ^ b[i]);
+        assert!(!leaked);
+
+        // The nonce is authenticated
+        let mut tampered = first;
+        tampered[0] ^= 1;
+        let mut store = MemoryCredentialStore::new();
+        store.register(b"alice", record(0)).unwrap();
+        assert_eq!(password_change_apply(&mut store, b"alice", &key, &tampered), Err(PakeError::AuthenticationFailure));
+        assert_eq!(password_change_apply(&mut store, b"alice", &key, &first), Ok(()));
+        assert_eq!(store.lookup(b"alice"), Ok(Some(record(1))));
+    }
+
+    #[test]
+    fn test_password_change_param_set() {
+        let mut rng = rand::thread_rng();
+        let mut record = record(1);
+        record.param_set ^= 1;
+        assert_eq!(password_change_request(&[0u8;KYBER_SYMBYTES], &record, &mut rng).err(), Some(PakeError::ParameterMismatch));
+    }
+}
@@ -3,9 +3,9 @@
 use crate::symmetric::KeccakState;
 
 pub const SHAKE128_RATE: usize = 168;
-const SHAKE256_RATE: usize = 136;
-const SHA3_256_RATE: usize = 136;
-const SHA3_512_RATE: usize = 72;
+pub const SHAKE256_RATE: usize = 136;
+pub const SHA3_256_RATE: usize = 136;
+pub const SHA3_512_RATE: usize = 72;
 const NROUNDS: usize = 24;
 
 fn rol(a: u64, offset: u64) -> u64 {
@@ -415,11 +415,41 @@ pub fn sha3_512(h: &mut [u8], input: &[u8], inlen: usize) {
 ///  - usize pos: position in current block to be absorbed
 ///  - usize r: rate in bytes (e.g., 168 for SHAKE128)
 ///  - u8 p: domain separation byte
-fn keccak_finalize(s: &mut [u64], pos: usize, r: usize, p: u8) {
+pub fn keccak_finalize(s: &mut [u64], pos: usize, r: usize, p: u8) {
     s[pos / 8] ^= (p as u64) << 8 * (pos % 8);
     s[r / 8 - 1] ^= 1u64 << 63;
 }
 
+/// Name:  keccak_absorb
+///
+/// Description: Absorb step of Keccak; incremental.
+///
+/// Arguments:   - u64 *s:   in/output Keccak state
+///  - usize pos: position in current block to be absorbed
+///  - usize r:  rate in bytes (e.g., 168 for SHAKE128)
+///  - const [u8] input:  input to be absorbed into s
+///
+/// Returns new position pos in current block
+pub fn keccak_absorb(s: &mut [u64], mut pos: usize, r: usize, input: &[u8]) -> usize {
+    let mut idx = 0usize;
+    let mut inlen = input.len();
+    while pos + inlen >= r {
+        for i in pos..r {
+            s[i / 8] ^= (input[idx] as u64) << (8 * (i % 8));
+            idx += 1;
+        }
+        inlen -= r - pos;
+        keccakf1600_statepermute(s);
+        pos = 0;
+    }
+
+    for i in pos..pos + inlen {
+        s[i / 8] ^= (input[idx] as u64) << (8 * (i % 8));
+        idx += 1;
+    }
+    pos + inlen
+}
+
 /// Name:  keccak_absorb_once
 ///
 /// Description: Absorb step of Keccak;
@@ -18,8 +18,10 @@ impl Default for Poly {
 
 // new() is nicer
 impl Poly {
-    pub fn new() -> Self {
-        Self::default()
+    pub const fn new() -> Self {
+        Poly {
+            coeffs: [0i16; KYBER_N],
+        }
     }
 }
 
@@ -60,6 +62,28 @@ pub fn poly_frombytes(r: &mut Poly, a: &[u8]) {
     }
 }
 
+/// Name:  poly_modulus_check
+///
+/// Description: FIPS 203 modulus check on a serialized polynomial; checks that
+///  every 12-bit coefficient is a canonical representative in {0,...,q-1}
+///
+/// Arguments:   - const [u8] a: input byte array (of KYBER_POLYBYTES bytes)
+///
+/// Returns 0 if all coefficients are canonical, 1 otherwise
+pub fn poly_modulus_check(a: &[u8]) -> u8 {
+    let (mut t0, mut t1);
+    let mut r = 0u16;
+
+    for i in 0..(KYBER_N / 2) {
+        t0 = (a[3 * i + 0] >> 0) as u16 | ((a[3 * i + 1] as u16) << 8) & 0xFFF;
+        t1 = (a[3 * i + 1] >> 4) as u16 | ((a[3 * i + 2] as u16) << 4) & 0xFFF;
+        // (q-1) - t underflows into the top bit exactly when t >= q
+        r |= (KYBER_Q as u16 - 1).wrapping_sub(t0);
+        r |= (KYBER_Q as u16 - 1).wrapping_sub(t1);
+    }
+    (r >> 15) as u8
+}
+
 /// Name:  poly_reduce
 ///
 /// Description: Applies Barrett reduction to all coefficients of a polynomial
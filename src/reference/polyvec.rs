@@ -11,7 +11,7 @@ pub struct Polyvec {
 impl Copy for Polyvec {}
 
 impl Polyvec {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Polyvec {
             vec: [Poly::new(); KYBER_K],
         }
@@ -43,6 +43,22 @@ pub fn polyvec_frombytes(r: &mut Polyvec, a: &[u8]) {
     }
 }
 
+/// Name:  polyvec_modulus_check
+///
+/// Description: FIPS 203 modulus check on a serialized vector of polynomials,
+///  i.e. checks that polyvec_tobytes(polyvec_frombytes(a)) == a
+///
+/// Arguments:   - const [u8] a: input byte array (of length KYBER_POLYVECBYTES)
+///
+/// Returns 0 if all coefficients are canonical, 1 otherwise
+pub fn polyvec_modulus_check(a: &[u8]) -> u8 {
+    let mut r = 0u8;
+    for i in 0..KYBER_K {
+        r |= poly_modulus_check(&a[i * KYBER_POLYBYTES..]);
+    }
+    r
+}
+
 /// Name:  polyvec_reduce
 ///
 /// Description: Applies Barrett reduction to each coefficient
@@ -0,0 +1,428 @@
+//! Session resumption without the KEM.
+//!
+//! After a full exchange whose key confirmation succeeded, the responder issues a ticket: the
+//! account id, the resumption secret rs = pake_export_key(key, "resumption") and an expiry,
+//! encrypted and authenticated under a long-term ticket key only the server knows. The client derives rs from
+//! its own copy of the session key and keeps it with the ticket.
+//!
+//! Resumption is a symmetric handshake:
+//! 1. client -> server: ticket || nonce_c
+//! 2. server -> client: nonce_s || server_tag || new ticket
+//! 3. client -> server: client_tag (checked with pake_resp_confirm)
+//!
+//! Both sides derive SHAKE256("CHIC-resume" || rs || nonce_c || nonce_s) = key || server_tag ||
+//! client_tag || rs', where rs' is the resumption secret of the new ticket. rs cannot be computed
+//! from rs', so once the old ticket and secret are deleted, the keys of earlier sessions stay safe
+//! even if the client state leaks later. Each ticket is accepted once (ReplayCache) and only
+//! until its expiry.
+//!
+//! The ticket is recorded in the replay cache as soon as resume_server accepts it, before the
+//! client has proven with client_tag that it knows rs. Whoever replays a captured first flight
+//! before the client does therefore uses up the ticket without learning anything, and the client
+//! gets PakeError::TicketReplayed and falls back to a full exchange.
+//!
+//! The ticket key protects every outstanding ticket: anyone who holds it and has recorded
+//! tickets recovers their resumption secrets, and from those and the recorded nonces the keys
+//! of every resumed session, past and future, along the whole ratchet. Keep it out of long-term
+//! storage and rotate it at least as often as the ticket lifetime, which also bounds what a
+//! compromise exposes; tickets issued under the previous key then fail to open and their
+//! clients run a full exchange.
+
+use rand_core::{CryptoRng, RngCore};
+use crate::api::pake_export_key;
+use crate::error::PakeError;
+use crate::limiter::Clock;
+use crate::params::KYBER_SYMBYTES;
+use crate::reference::fips202::shake256;
+use crate::reference::verify::verify;
+use crate::rng::randombytes;
+
+/// Size of the account id carried in a ticket
+pub const ACCOUNT_ID_BYTES: usize = KYBER_SYMBYTES;
+/// Size of the encrypted part of a ticket: expiry || account id || rs
+const TICKET_PLAINTEXT_BYTES: usize = 8 + ACCOUNT_ID_BYTES + KYBER_SYMBYTES;
+/// Size of a ticket: ticket id || encrypted expiry, account id and rs || tag
+pub const TICKET_BYTES: usize = KYBER_SYMBYTES + TICKET_PLAINTEXT_BYTES + KYBER_SYMBYTES;
+/// Size of the first resumption flight: ticket || nonce_c
+pub const RESUME_HELLO_BYTES: usize = TICKET_BYTES + KYBER_SYMBYTES;
+/// Size of the second resumption flight: nonce_s || server_tag || new ticket
+pub const RESUME_REPLY_BYTES: usize = 2 * KYBER_SYMBYTES + TICKET_BYTES;
+
+/// Reply to the client, session key, expected client tag and the account id of the ticket
+pub type ResumeReply = Result<([u8;RESUME_REPLY_BYTES], [u8;KYBER_SYMBYTES], [u8;KYBER_SYMBYTES], [u8;ACCOUNT_ID_BYTES]), PakeError>;
+
+const RESUMPTION_LABEL: &[u8] = b"resumption";
+const TICKET_ENC_LABEL: &[u8] = b"ticket encryption";
+const TICKET_MAC_LABEL: &[u8] = b"ticket authentication";
+const RESUME_LABEL: &[u8;11] = b"CHIC-resume";
+
+/// Remembers the tickets that have been used, until they expire
+pub trait ReplayCache {
+    /// Records ticket id, valid until expiry_ms. Returns false if it was already recorded.
+    fn insert(&mut self, id: &[u8;KYBER_SYMBYTES], expiry_ms: u64, now_ms: u64) -> bool;
+}
+
+/// Replay cache kept in memory; expired entries are dropped on insertion
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct MemoryReplayCache {
+    seen: std::collections::HashMap<[u8;KYBER_SYMBYTES], u64>,
+}
+
+#[cfg(feature = "std")]
+impl MemoryReplayCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl ReplayCache for MemoryReplayCache {
+    fn insert(&mut self, id: &[u8;KYBER_SYMBYTES], expiry_ms: u64, now_ms: u64) -> bool {
+        self.seen.retain(|_, expiry| *expiry >= now_ms);
+        self.seen.insert(*id, expiry_ms).is_none()
+    }
+}
+
+/// Client side resumption state: the ticket and the matching resumption secret
+pub struct ResumptionState {
+    pub ticket: [u8;TICKET_BYTES],
+    secret: [u8;KYBER_SYMBYTES],
+}
+
+impl ResumptionState {
+    /// Stores a ticket received after a full exchange with session key key
+    pub fn new(key: &[u8;KYBER_SYMBYTES], ticket: &[u8;TICKET_BYTES]) -> Result<Self, PakeError> {
+        Ok(ResumptionState { ticket: *ticket, secret: pake_export_key(key, RESUMPTION_LABEL)? })
+    }
+}
+
+/// Client side state between the first and the second resumption flight
+pub struct ResumeClientPending {
+    nonce: [u8;KYBER_SYMBYTES],
+    secret: [u8;KYBER_SYMBYTES],
+}
+
+/// key || server_tag || client_tag || rs'
+struct ResumeKeys {
+    key: [u8;KYBER_SYMBYTES],
+    server_tag: [u8;KYBER_SYMBYTES],
+    client_tag: [u8;KYBER_SYMBYTES],
+    next_secret: [u8;KYBER_SYMBYTES],
+}
+
+fn resume_keys(secret: &[u8;KYBER_SYMBYTES], nonce_c: &[u8;KYBER_SYMBYTES], nonce_s: &[u8;KYBER_SYMBYTES]) -> ResumeKeys {
+    let mut hashin = [0u8;RESUME_LABEL.len()+3*KYBER_SYMBYTES];
+    let mut out = [0u8;4*KYBER_SYMBYTES];
+    hashin[..RESUME_LABEL.len()].copy_from_slice(RESUME_LABEL);
+    hashin[RESUME_LABEL.len()..RESUME_LABEL.len()+KYBER_SYMBYTES].copy_from_slice(secret);
+    hashin[RESUME_LABEL.len()+KYBER_SYMBYTES..RESUME_LABEL.len()+2*KYBER_SYMBYTES].copy_from_slice(nonce_c);
+    hashin[RESUME_LABEL.len()+2*KYBER_SYMBYTES..].copy_from_slice(nonce_s);
+    shake256(&mut out, 4*KYBER_SYMBYTES, &hashin, hashin.len());
+    let mut keys = ResumeKeys {
+        key: [0u8;KYBER_SYMBYTES],
+        server_tag: [0u8;KYBER_SYMBYTES],
+        client_tag: [0u8;KYBER_SYMBYTES],
+        next_secret: [0u8;KYBER_SYMBYTES],
+    };
+    keys.key.copy_from_slice(&out[..KYBER_SYMBYTES]);
+    keys.server_tag.copy_from_slice(&out[KYBER_SYMBYTES..2*KYBER_SYMBYTES]);
+    keys.client_tag.copy_from_slice(&out[2*KYBER_SYMBYTES..3*KYBER_SYMBYTES]);
+    keys.next_secret.copy_from_slice(&out[3*KYBER_SYMBYTES..]);
+    keys
+}
+
+/// XORs the SHAKE256(enc_key || id) keystream into data
+fn ticket_keystream(data: &mut [u8;TICKET_PLAINTEXT_BYTES], enc_key: &[u8;KYBER_SYMBYTES], id: &[u8;KYBER_SYMBYTES]) {
+    let mut hashin = [0u8;2*KYBER_SYMBYTES];
+    let mut stream = [0u8;TICKET_PLAINTEXT_BYTES];
+    hashin[..KYBER_SYMBYTES].copy_from_slice(enc_key);
+    hashin[KYBER_SYMBYTES..].copy_from_slice(id);
+    shake256(&mut stream, TICKET_PLAINTEXT_BYTES, &hashin, hashin.len());
+    for (d, s) in data.iter_mut().zip(stream.iter()) {
+        *d ^= s;
+    }
+}
+
+/// tag = SHAKE256(mac_key || id || ct)
+fn ticket_mac(mac_key: &[u8;KYBER_SYMBYTES], ticket: &[u8;TICKET_BYTES]) -> [u8;KYBER_SYMBYTES] {
+    let mut hashin = [0u8;KYBER_SYMBYTES+TICKET_BYTES-KYBER_SYMBYTES];
+    let mut tag = [0u8;KYBER_SYMBYTES];
+    hashin[..KYBER_SYMBYTES].copy_from_slice(mac_key);
+    hashin[KYBER_SYMBYTES..].copy_from_slice(&ticket[..TICKET_BYTES-KYBER_SYMBYTES]);
+    shake256(&mut tag, KYBER_SYMBYTES, &hashin, hashin.len());
+    tag
+}
+
+fn seal_ticket<R>(ticket_key: &[u8;KYBER_SYMBYTES], account: &[u8;ACCOUNT_ID_BYTES], secret: &[u8;KYBER_SYMBYTES], expiry_ms: u64, rng: &mut R) -> Result<[u8;TICKET_BYTES], PakeError>
+where
+    R: CryptoRng + RngCore
+{
+    let enc_key = pake_export_key(ticket_key, TICKET_ENC_LABEL)?;
+    let mac_key = pake_export_key(ticket_key, TICKET_MAC_LABEL)?;
+    let mut id = [0u8;KYBER_SYMBYTES];
+    randombytes(&mut id, KYBER_SYMBYTES, rng)?;
+
+    let mut plaintext = [0u8;TICKET_PLAINTEXT_BYTES];
+    plaintext[..8].copy_from_slice(&expiry_ms.to_le_bytes());
+    plaintext[8..8+ACCOUNT_ID_BYTES].copy_from_slice(account);
+    plaintext[8+ACCOUNT_ID_BYTES..].copy_from_slice(secret);
+    ticket_keystream(&mut plaintext, &enc_key, &id);
+
+    let mut ticket = [0u8;TICKET_BYTES];
+    ticket[..KYBER_SYMBYTES].copy_from_slice(&id);
+    ticket[KYBER_SYMBYTES..KYBER_SYMBYTES+TICKET_PLAINTEXT_BYTES].copy_from_slice(&plaintext);
+    let tag = ticket_mac(&mac_key, &ticket);
+    ticket[KYBER_SYMBYTES+TICKET_PLAINTEXT_BYTES..].copy_from_slice(&tag);
+    Ok(ticket)
+}
+
+/// Contents of an authentic ticket
+struct OpenTicket {
+    id: [u8;KYBER_SYMBYTES],
+    expiry_ms: u64,
+    account: [u8;ACCOUNT_ID_BYTES],
+    secret: [u8;KYBER_SYMBYTES],
+}
+
+fn open_ticket(ticket_key: &[u8;KYBER_SYMBYTES], ticket: &[u8;TICKET_BYTES]) -> Result<OpenTicket, PakeError> {
+    let enc_key = pake_export_key(ticket_key, TICKET_ENC_LABEL)?;
+    let mac_key = pake_export_key(ticket_key, TICKET_MAC_LABEL)?;
+    let tag = ticket_mac(&mac_key, ticket);
+    if verify(&tag, &ticket[KYBER_SYMBYTES+TICKET_PLAINTEXT_BYTES..], KYBER_SYMBYTES) != 0 {
+        return Err(PakeError::AuthenticationFailure);
+    }
+
+    let mut id = [0u8;KYBER_SYMBYTES];
+    let mut plaintext = [0u8;TICKET_PLAINTEXT_BYTES];
+    id.copy_from_slice(&ticket[..KYBER_SYMBYTES]);
+    plaintext.copy_from_slice(&ticket[KYBER_SYMBYTES..KYBER_SYMBYTES+TICKET_PLAINTEXT_BYTES]);
+    ticket_keystream(&mut plaintext, &enc_key, &id);
+
+    let mut expiry = [0u8;8];
+    let mut account = [0u8;ACCOUNT_ID_BYTES];
+    let mut secret = [0u8;KYBER_SYMBYTES];
+    expiry.copy_from_slice(&plaintext[..8]);
+    account.copy_from_slice(&plaintext[8..8+ACCOUNT_ID_BYTES]);
+    secret.copy_from_slice(&plaintext[8+ACCOUNT_ID_BYTES..]);
+    Ok(OpenTicket { id, expiry_ms: u64::from_le_bytes(expiry), account, secret })
+}
+
+/// Name:  ticket_issue
+///
+/// Description: Run by the responder after a full exchange whose key confirmation succeeded.
+///
+/// Arguments:   - [u8] ticket_key: the server's long-term ticket key (of length KYBER_SYMBYTES)
+///              - [u8] account: the account the session was run for (of length ACCOUNT_ID_BYTES),
+///                e.g. a database key or a hash of the username
+///              - [u8] key: the session key (of length KYBER_SYMBYTES)
+///              - clock: the server's clock
+///              - lifetime_ms: how long the ticket can be used
+///              - rng: the RNG drawing the ticket id
+///
+/// Returns the ticket (of length TICKET_BYTES)
+pub fn ticket_issue<C,R>(ticket_key: &[u8;KYBER_SYMBYTES], account: &[u8;ACCOUNT_ID_BYTES], key: &[u8;KYBER_SYMBYTES], clock: &C, lifetime_ms: u64, rng: &mut R) -> Result<[u8;TICKET_BYTES], PakeError>
+where
+    C: Clock,
+    R: CryptoRng + RngCore
+{
+    let secret = pake_export_key(key, RESUMPTION_LABEL)?;
+    seal_ticket(ticket_key, account, &secret, clock.now_ms().saturating_add(lifetime_ms), rng)
+}
+
+/// First resumption flight, run by the client. Consumes the resumption state, tickets are single use.
+pub fn resume_client_hello<R>(state: ResumptionState, rng: &mut R) -> Result<([u8;RESUME_HELLO_BYTES], ResumeClientPending), PakeError>
+where
+    R: CryptoRng + RngCore
+{
+    let mut nonce = [0u8;KYBER_SYMBYTES];
+    randombytes(&mut nonce, KYBER_SYMBYTES, rng)?;
+    let mut hello = [0u8;RESUME_HELLO_BYTES];
+    hello[..TICKET_BYTES].copy_from_slice(&state.ticket);
+    hello[TICKET_BYTES..].copy_from_slice(&nonce);
+    Ok((hello, ResumeClientPending { nonce, secret: state.secret }))
+}
+
+/// Name:  resume_server
+///
+/// Description: Second resumption flight, run by the server. Checks the ticket and issues the
+///  next one.
+///
+/// Arguments:   - [u8] ticket_key: the server's long-term ticket key (of length KYBER_SYMBYTES)
+///              - clock: the server's clock
+///              - cache: the replay cache
+///              - [u8] hello: the client's first flight (of length RESUME_HELLO_BYTES)
+///              - lifetime_ms: lifetime of the new ticket
+///              - rng: the RNG drawing the nonce and the new ticket id
+///
+/// Returns the reply (of length RESUME_REPLY_BYTES), the session key, the key confirmation
+///  tag expected from the client and the account id the ticket was issued for. The new ticket
+///  is for the same account. The ticket is recorded in cache before the client's tag is
+///  checked, see the module documentation. Fails with PakeError::AuthenticationFailure for a ticket not
+///  issued under ticket_key, PakeError::TicketExpired and PakeError::TicketReplayed.
+pub fn resume_server<C,RC,R>(ticket_key: &[u8;KYBER_SYMBYTES], clock: &C, cache: &mut RC, hello: &[u8;RESUME_HELLO_BYTES], lifetime_ms: u64, rng: &mut R) -> ResumeReply
+where
+    C: Clock,
+    RC: ReplayCache,
+    R: CryptoRng + RngCore
+{
+    let mut ticket = [0u8;TICKET_BYTES];
+    let mut nonce_c = [0u8;KYBER_SYMBYTES];
+    ticket.copy_from_slice(&hello[..TICKET_BYTES]);
+    nonce_c.copy_from_slice(&hello[TICKET_BYTES..]);
+
+    let opened = open_ticket(ticket_key, &ticket)?;
+    let now = clock.now_ms();
+    if now > opened.expiry_ms {
+        return Err(PakeError::TicketExpired);
+    }
+    if !cache.insert(&opened.id, opened.expiry_ms, now) {
+        return Err(PakeError::TicketReplayed);
+    }
+
+    let mut nonce_s = [0u8;KYBER_SYMBYTES];
+    randombytes(&mut nonce_s, KYBER_SYMBYTES, rng)?;
+    let keys = resume_keys(&opened.secret, &nonce_c, &nonce_s);
+    let next_ticket = seal_ticket(ticket_key, &opened.account, &keys.next_secret, now.saturating_add(lifetime_ms), rng)?;
+
+    let mut reply = [0u8;RESUME_REPLY_BYTES];
+    reply[..KYBER_SYMBYTES].copy_from_slice(&nonce_s);
+    reply[KYBER_SYMBYTES..2*KYBER_SYMBYTES].copy_from_slice(&keys.server_tag);
+    reply[2*KYBER_SYMBYTES..].copy_from_slice(&next_ticket);
+    Ok((reply, keys.key, keys.client_tag, opened.account))
+}
+
+/// Run by the client on the server's reply. Returns the session key, the key confirmation tag
+/// to send to the server and the resumption state for the next reconnect, or
+/// PakeError::AuthenticationFailure if the server does not know the resumption secret.
+pub fn resume_client_finish(pending: ResumeClientPending, reply: &[u8;RESUME_REPLY_BYTES]) -> Result<([u8;KYBER_SYMBYTES], [u8;KYBER_SYMBYTES], ResumptionState), PakeError> {
+    let mut nonce_s = [0u8;KYBER_SYMBYTES];
+    nonce_s.copy_from_slice(&reply[..KYBER_SYMBYTES]);
+    let keys = resume_keys(&pending.secret, &pending.nonce, &nonce_s);
+    if verify(&keys.server_tag, &reply[KYBER_SYMBYTES..2*KYBER_SYMBYTES], KYBER_SYMBYTES) != 0 {
+        return Err(PakeError::AuthenticationFailure);
+    }
+    let mut ticket = [0u8;TICKET_BYTES];
+    ticket.copy_from_slice(&reply[2*KYBER_SYMBYTES..]);
+    Ok((keys.key, keys.client_tag, ResumptionState { ticket, secret: keys.next_secret }))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+
+    use rand::RngCore;
+    use crate::api::*;
+    use crate::params::*;
+    use crate::testing::*;
+    use super::*;
+
+    const LIFETIME: u64 = 60_000;
+    const ACCOUNT: [u8;ACCOUNT_ID_BYTES] = [7u8;ACCOUNT_ID_BYTES];
+
+    /// Full exchange, then the ticket is issued. Returns the client's state and the server's ticket key.
+    fn full_exchange(clock: &MockClock) -> (ResumptionState, [u8;KYBER_SYMBYTES]) {
+        let mut rng = rand::thread_rng();
+        let mut pw = [0u8;KYBER_SYMBYTES];
+        let mut ticket_key = [0u8;KYBER_SYMBYTES];
+        rng.fill_bytes(&mut pw);
+        rng.fill_bytes(&mut ticket_key);
+
+        let (enc_pk, pk, sk) = pake_init_start(&pw, &mut rng, keypair_func).unwrap();
+        let (sid, msg1) = pake_split_msg1(&enc_pk);
+        let (msg2, key_s, expected_tag) = pake_resp(&sid, &msg1, &pw, &mut rng, encapsulate_func).unwrap();
+        let (key_c, tag) = pake_init_end(&msg2, &sid, &msg1, &pk, &sk, decapsulate_func).unwrap();
+        pake_resp_confirm(&expected_tag, &tag).unwrap();
+
+        let ticket = ticket_issue(&ticket_key, &ACCOUNT, &key_s, clock, LIFETIME, &mut rng).unwrap();
+        (ResumptionState::new(&key_c, &ticket).unwrap(), ticket_key)
+    }
+
+    #[test]
+    fn test_resumption_ratchet() {
+        let mut rng = rand::thread_rng();
+        let clock = MockClock::default();
+        let mut cache = MemoryReplayCache::new();
+        let (mut state, ticket_key) = full_exchange(&clock);
+
+        let mut keys = std::vec::Vec::new();
+        for _ in 0..3 {
+            let (hello, pending) = resume_client_hello(state, &mut rng).unwrap();
+            let (reply, key_s, expected_tag, account) = resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).unwrap();
+            let (key_c, tag, next) = resume_client_finish(pending, &reply).unwrap();
+            assert_eq!(key_c, key_s);
+            assert_eq!(account, ACCOUNT);
+            assert_eq!(pake_resp_confirm(&expected_tag, &tag), Ok(()));
+            assert!(!keys.contains(&key_c));
+            keys.push(key_c);
+            state = next;
+        }
+    }
+
+    #[test]
+    fn test_ticket_replay_and_expiry() {
+        let mut rng = rand::thread_rng();
+        let clock = MockClock::default();
+        let mut cache = MemoryReplayCache::new();
+        let (state, ticket_key) = full_exchange(&clock);
+        let ticket = state.ticket;
+
+        let (hello, _) = resume_client_hello(state, &mut rng).unwrap();
+        assert!(resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).is_ok());
+        assert_eq!(resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).err(), Some(PakeError::TicketReplayed));
+
+        // Replay with a fresh client nonce
+        let (hello, _) = resume_client_hello(ResumptionState { ticket, secret: [0u8;KYBER_SYMBYTES] }, &mut rng).unwrap();
+        assert_eq!(resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).err(), Some(PakeError::TicketReplayed));
+
+        // Whoever sends a captured first flight before the client uses up the ticket; the client
+        // is refused and falls back to a full exchange
+        let (state, ticket_key) = full_exchange(&clock);
+        let (hello, _) = resume_client_hello(state, &mut rng).unwrap();
+        assert!(resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).is_ok());
+        assert_eq!(resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).err(), Some(PakeError::TicketReplayed));
+
+        let (state, ticket_key) = full_exchange(&clock);
+        clock.set(LIFETIME + 1);
+        let (hello, _) = resume_client_hello(state, &mut rng).unwrap();
+        assert_eq!(resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).err(), Some(PakeError::TicketExpired));
+
+        // Expired entries leave the cache
+        let (state, ticket_key) = full_exchange(&clock);
+        let (hello, _) = resume_client_hello(state, &mut rng).unwrap();
+        assert!(resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).is_ok());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_forged_tickets() {
+        let mut rng = rand::thread_rng();
+        let clock = MockClock::default();
+        let mut cache = MemoryReplayCache::new();
+
+        // Modified ticket, e.g. an extended expiry
+        let (state, ticket_key) = full_exchange(&clock);
+        let (mut hello, _) = resume_client_hello(state, &mut rng).unwrap();
+        hello[KYBER_SYMBYTES] ^= 1;
+        assert_eq!(resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).err(), Some(PakeError::AuthenticationFailure));
+
+        // Ticket of another server
+        let (state, _) = full_exchange(&clock);
+        let (hello, _) = resume_client_hello(state, &mut rng).unwrap();
+        assert_eq!(resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).err(), Some(PakeError::AuthenticationFailure));
+
+        // A server that does not know the resumption secret is rejected by the client
+        let (state, ticket_key) = full_exchange(&clock);
+        let (hello, pending) = resume_client_hello(state, &mut rng).unwrap();
+        let (mut reply, _, _, _) = resume_server(&ticket_key, &clock, &mut cache, &hello, LIFETIME, &mut rng).unwrap();
+        reply[KYBER_SYMBYTES] ^= 1;
+        assert_eq!(resume_client_finish(pending, &reply).err(), Some(PakeError::AuthenticationFailure));
+    }
+}
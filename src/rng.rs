@@ -1,15 +1,104 @@
-use crate::KyberError;
+use crate::PakeError;
+use crate::reference::fips202::{keccak_absorb_once, keccak_squeezeblocks, SHAKE256_RATE};
 use rand_core::*;
 
 /// Fills buffer x with len bytes, RNG must satisfy the
 /// RngCore trait and CryptoRng marker trait requirements
-#[allow(dead_code)]
-pub fn randombytes<R>(x: &mut [u8], len: usize, rng: &mut R) -> Result<(), KyberError>
+pub fn randombytes<R>(x: &mut [u8], len: usize, rng: &mut R) -> Result<(), PakeError>
 where
     R: RngCore + CryptoRng,
 {
     match rng.try_fill_bytes(&mut x[..len]) {
         Ok(_) => Ok(()),
-        Err(_) => Err(KyberError::RandomBytesGeneration),
+        Err(_) => Err(PakeError::RandomBytesGeneration),
+    }
+}
+
+/// Domain separation label of ShakeRng
+const SHAKE_RNG_LABEL: &[u8;8] = b"CHIC-rng";
+
+/// Deterministic RNG whose output is the SHAKE256 stream of "CHIC-rng" || seed.
+///
+/// Passing a ShakeRng instead of a system RNG makes a run reproducible: the same seed always
+/// yields the same sid, keys and ciphertexts, so transcripts can be logged, replayed and diffed.
+/// Meant for tests, known answer tests and debugging; a seed used twice in production reuses the
+/// KEM randomness.
+#[derive(Clone)]
+pub struct ShakeRng {
+    state: [u64;25],
+    buf: [u8;SHAKE256_RATE],
+    pos: usize,
+}
+
+impl SeedableRng for ShakeRng {
+    type Seed = [u8;32];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        let mut input = [0u8;SHAKE_RNG_LABEL.len()+32];
+        input[..SHAKE_RNG_LABEL.len()].copy_from_slice(SHAKE_RNG_LABEL);
+        input[SHAKE_RNG_LABEL.len()..].copy_from_slice(&seed);
+        let mut state = [0u64;25];
+        keccak_absorb_once(&mut state, SHAKE256_RATE, &input, input.len(), 0x1F);
+        ShakeRng { state, buf: [0u8;SHAKE256_RATE], pos: SHAKE256_RATE }
+    }
+}
+
+impl RngCore for ShakeRng {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let mut idx = 0;
+        while idx < dest.len() {
+            if self.pos == SHAKE256_RATE {
+                keccak_squeezeblocks(&mut self.buf, 1, &mut self.state, SHAKE256_RATE);
+                self.pos = 0;
+            }
+            let n = (dest.len() - idx).min(SHAKE256_RATE - self.pos);
+            dest[idx..idx+n].copy_from_slice(&self.buf[self.pos..self.pos+n]);
+            self.pos += n;
+            idx += n;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for ShakeRng {}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::reference::fips202::shake256;
+    use super::*;
+
+    #[test]
+    fn test_shake_rng() {
+        // The stream is SHAKE256("CHIC-rng" || seed), however it is read
+        let seed = [7u8;32];
+        let mut input = [0u8;40];
+        input[..8].copy_from_slice(SHAKE_RNG_LABEL);
+        input[8..].copy_from_slice(&seed);
+        let mut expected = [0u8;3*SHAKE256_RATE];
+        shake256(&mut expected, 3*SHAKE256_RATE, &input, input.len());
+
+        let mut out = [0u8;3*SHAKE256_RATE];
+        let mut rng = ShakeRng::from_seed(seed);
+        for chunk in out.chunks_mut(37) {
+            rng.fill_bytes(chunk);
+        }
+        assert_eq!(out, expected);
+
+        let mut other = [0u8;32];
+        ShakeRng::from_seed([8u8;32]).fill_bytes(&mut other);
+        assert_ne!(other, expected[..32]);
     }
 }
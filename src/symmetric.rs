@@ -8,6 +8,17 @@ use sha2::{Digest, Sha256, Sha512};
 
 pub const XOF_BLOCKBYTES: usize = SHAKE128_RATE;
 
+/// Hash backend behind hash_g and hash_h in this build
+pub const HASH_BACKEND: &str = if cfg!(all(feature = "sha2", feature = "small_sha")) {
+    "sha2-small"
+} else if cfg!(feature = "sha2") {
+    "sha2"
+} else if cfg!(feature = "libjade_sha") {
+    "sha3-libjade"
+} else {
+    "sha3"
+};
+
 pub type XofState = KeccakState;
 #[derive(Copy, Clone)]
 pub struct KeccakState {
@@ -38,7 +49,7 @@ pub fn hash_h(out: &mut [u8;32], input: &[u8], inlen: usize) {
     sha3_256(out, input, inlen);
 }
 
-#[cfg(not(feature = "sha2"))]
+#[cfg(all(not(feature = "sha2"), any(test, feature = "bench-internals")))]
 pub fn hash_g(out: &mut [u8], input: &[u8], inlen: usize) {
     sha3_512(out, input, inlen);
 }
@@ -57,7 +68,7 @@ pub fn hash_h(out: &mut [u8;32], input: &[u8], inlen: usize) {
     }
 }
 
-#[cfg(feature = "sha2")]
+#[cfg(all(feature = "sha2", any(test, feature = "bench-internals")))]
 pub fn hash_g(out: &mut [u8], input: &[u8], inlen: usize) {
     if cfg!(feature = "small_sha") {
         let out2 = sha512(input);
@@ -71,6 +82,135 @@ pub fn hash_g(out: &mut [u8], input: &[u8], inlen: usize) {
     }
 }
 
+/// Incremental hash_h, for inputs that are not in one buffer: new, update any number of times,
+/// finalize. The digest equals hash_h of the concatenated input. With libjade_sha, the reference
+/// SHA3-256 is used, which gives the same digest.
+pub struct HashH {
+    #[cfg(not(feature = "sha2"))]
+    state: KeccakState,
+    #[cfg(all(feature = "sha2", feature = "small_sha"))]
+    state: crate::hic::sha256::Sha256,
+    #[cfg(all(feature = "sha2", not(feature = "small_sha")))]
+    state: Sha256,
+}
+
+impl HashH {
+    pub fn new() -> Self {
+        HashH {
+            #[cfg(not(feature = "sha2"))]
+            state: KeccakState::new(),
+            #[cfg(all(feature = "sha2", feature = "small_sha"))]
+            state: crate::hic::sha256::Sha256::new(),
+            #[cfg(all(feature = "sha2", not(feature = "small_sha")))]
+            state: Sha256::new(),
+        }
+    }
+
+    pub fn update(&mut self, input: &[u8]) {
+        #[cfg(not(feature = "sha2"))] {
+            self.state.pos = keccak_absorb(&mut self.state.s, self.state.pos, SHA3_256_RATE, input);
+        }
+        #[cfg(feature = "sha2")]
+        self.state.update(input);
+    }
+
+    pub fn finalize(self, out: &mut [u8;32]) {
+        #[cfg(not(feature = "sha2"))] {
+            let mut s = self.state.s;
+            keccak_finalize(&mut s, self.state.pos, SHA3_256_RATE, 0x06);
+            keccakf1600_statepermute(&mut s);
+            for i in 0..4 {
+                store64(&mut out[8 * i..], s[i]);
+            }
+        }
+        #[cfg(feature = "sha2")]
+        out.copy_from_slice(&self.state.finalize()[..32]);
+    }
+}
+
+impl Default for HashH {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Incremental hash_g, see HashH
+pub struct HashG {
+    #[cfg(not(feature = "sha2"))]
+    state: KeccakState,
+    #[cfg(all(feature = "sha2", feature = "small_sha"))]
+    state: crate::hic::sha512::Sha512,
+    #[cfg(all(feature = "sha2", not(feature = "small_sha")))]
+    state: Sha512,
+}
+
+impl HashG {
+    pub fn new() -> Self {
+        HashG {
+            #[cfg(not(feature = "sha2"))]
+            state: KeccakState::new(),
+            #[cfg(all(feature = "sha2", feature = "small_sha"))]
+            state: crate::hic::sha512::Sha512::new(),
+            #[cfg(all(feature = "sha2", not(feature = "small_sha")))]
+            state: Sha512::new(),
+        }
+    }
+
+    pub fn update(&mut self, input: &[u8]) {
+        #[cfg(not(feature = "sha2"))] {
+            self.state.pos = keccak_absorb(&mut self.state.s, self.state.pos, SHA3_512_RATE, input);
+        }
+        #[cfg(feature = "sha2")]
+        self.state.update(input);
+    }
+
+    pub fn finalize(self, out: &mut [u8;64]) {
+        #[cfg(not(feature = "sha2"))] {
+            let mut s = self.state.s;
+            keccak_finalize(&mut s, self.state.pos, SHA3_512_RATE, 0x06);
+            keccakf1600_statepermute(&mut s);
+            for i in 0..8 {
+                store64(&mut out[8 * i..], s[i]);
+            }
+        }
+        #[cfg(feature = "sha2")]
+        out.copy_from_slice(&self.state.finalize()[..64]);
+    }
+}
+
+impl Default for HashG {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Incremental kdf, see HashH
+pub struct Kdf {
+    state: KeccakState,
+}
+
+impl Kdf {
+    pub fn new() -> Self {
+        Kdf { state: KeccakState::new() }
+    }
+
+    pub fn update(&mut self, input: &[u8]) {
+        self.state.pos = keccak_absorb(&mut self.state.s, self.state.pos, SHAKE256_RATE, input);
+    }
+
+    pub fn finalize(self, out: &mut [u8;KYBER_SSBYTES]) {
+        let mut s = self.state.s;
+        keccak_finalize(&mut s, self.state.pos, SHAKE256_RATE, 0x1F);
+        keccak_squeeze(out, KYBER_SSBYTES, &mut s, SHAKE256_RATE, SHAKE256_RATE);
+    }
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub fn xof_absorb(state: &mut XofState, input: &[u8], x: u8, y: u8) {
     kyber_shake128_absorb(state, &input, x, y);
 }
@@ -83,6 +223,7 @@ pub fn prf(out: &mut [u8], outbytes: usize, key: &[u8], nonce: u8) {
     shake256_prf(out, outbytes, &key, nonce);
 }
 
+#[cfg(test)]
 pub fn kdf(out: &mut [u8], input: &[u8], inlen: usize) {
     shake256(out, KYBER_SSBYTES, input, inlen);
 }
@@ -132,3 +273,37 @@ fn shake256_prf(output: &mut [u8], outlen: usize, key: &[u8], nonce: u8) {
     extkey[KYBER_SYMBYTES] = nonce;
     shake256(output, outlen, &extkey, KYBER_SYMBYTES + 1);
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_incremental_hashes() {
+        // Chunks that straddle the SHA3-512, SHA3-256 and SHAKE256 block boundaries
+        let mut input = [0u8;3*SHA3_256_RATE+5];
+        for (i, b) in input.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        for len in [0, 1, SHA3_512_RATE, SHA3_256_RATE, input.len()] {
+            let (mut h, mut g, mut k) = (HashH::new(), HashG::new(), Kdf::new());
+            for chunk in input[..len].chunks(37) {
+                h.update(chunk);
+                g.update(chunk);
+                k.update(chunk);
+            }
+            let (mut out_h, mut out_g, mut out_k) = ([0u8;32], [0u8;64], [0u8;KYBER_SSBYTES]);
+            let (mut expected_h, mut expected_g, mut expected_k) = ([0u8;32], [0u8;64], [0u8;KYBER_SSBYTES]);
+            h.finalize(&mut out_h);
+            g.finalize(&mut out_g);
+            k.finalize(&mut out_k);
+            hash_h(&mut expected_h, &input[..len], len);
+            hash_g(&mut expected_g, &input[..len], len);
+            kdf(&mut expected_k, &input[..len], len);
+            assert_eq!(out_h, expected_h);
+            assert_eq!(out_g, expected_g);
+            assert_eq!(out_k, expected_k);
+        }
+    }
+}
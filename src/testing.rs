@@ -0,0 +1,42 @@
+//! Fixtures shared by the unit tests: the KEM of pqc_kyber in the closure shape the API takes,
+//! and a clock the tests control.
+
+use rand_core::{CryptoRng, RngCore};
+use crate::error::PakeError;
+use crate::params::*;
+
+pub fn keypair_func<R: CryptoRng + RngCore>(rng: &mut R) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError> {
+    let keypair = pqc_kyber::keypair(rng)?;
+    Ok((keypair.public, keypair.secret))
+}
+
+pub fn encapsulate_func<R: CryptoRng + RngCore>(pk: &[u8;KYBER_PUBLICKEYBYTES], rng: &mut R) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError> {
+    Ok(pqc_kyber::encapsulate(pk, rng)?)
+}
+
+pub fn decapsulate_func(ct: &[u8;KYBER_CIPHERTEXTBYTES], sk: &[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError> {
+    Ok(pqc_kyber::decapsulate(ct, sk)?)
+}
+
+/// Clock that only moves when told to; clones share the same time
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct MockClock(std::rc::Rc<core::cell::Cell<u64>>);
+
+#[cfg(feature = "std")]
+impl MockClock {
+    pub fn set(&self, ms: u64) {
+        self.0.set(ms);
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.0.set(self.0.get() + ms);
+    }
+}
+
+#[cfg(feature = "std")]
+impl crate::limiter::Clock for MockClock {
+    fn now_ms(&self) -> u64 {
+        self.0.get()
+    }
+}
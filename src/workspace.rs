@@ -0,0 +1,218 @@
+//! Low-memory entry points for targets with small stacks, such as Cortex-M0.
+//!
+//! The polynomial vectors and XOF buffer of the HIC and the responder's recovered public key live
+//! in a PakeWorkspace provided by the caller, which can be a static since PakeWorkspace::new is a
+//! const fn. The KEM closures write into the caller's buffers instead of returning arrays, and the
+//! transcript is hashed as a stream. pake_init_end and pake_init_end_implicit need no workspace,
+//! they only keep the hash states and the decapsulated secret on the stack.
+//!
+//! The peak stack usage of every stage, excluding the KEM closures, is bounded by the *_STACK_MAX
+//! constants below, with some headroom over what optimized x86_64 builds use. Unoptimized builds
+//! use several times more, so embedded targets should build the crate with optimizations.
+//! `cargo test --release --features std` enforces the bounds, and CI runs it.
+//!
+//! The bounds are host measurements only. Frame layout, register count and inlining differ on
+//! other targets, so they are not valid for Thumb; measure the stack usage on the device, e.g.
+//! by painting the stack, before sizing it from these constants.
+
+use rand_core::{CryptoRng, RngCore};
+use crate::chic::{bind_sid, init_start_in, resp_in};
+use crate::error::PakeError;
+use crate::hic::HicWorkspace;
+use crate::params::*;
+use crate::rng::randombytes;
+
+/// Peak stack usage of pake_init_start_in on x86_64, excluding the keypair closure
+pub const PAKE_INIT_START_STACK_MAX: usize = 3 * 1024;
+/// Peak stack usage of pake_resp_in on x86_64, excluding the encapsulation closure
+pub const PAKE_RESP_STACK_MAX: usize = 3 * 1024;
+/// Peak stack usage of pake_init_end and pake_init_end_implicit on x86_64, excluding the decapsulation closure
+pub const PAKE_INIT_END_STACK_MAX: usize = 3 * 1024;
+
+/// Scratch memory of the low-memory entry points. Holds no state between calls, so one workspace
+/// can be shared by all stages and sessions that do not run concurrently.
+pub struct PakeWorkspace {
+    pub(crate) hic: HicWorkspace,
+    pub(crate) pk: [u8;KYBER_PUBLICKEYBYTES],
+}
+
+impl PakeWorkspace {
+    pub const fn new() -> Self {
+        PakeWorkspace { hic: HicWorkspace::new(), pk: [0u8;KYBER_PUBLICKEYBYTES] }
+    }
+}
+
+impl Default for PakeWorkspace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Like pake_init_start, with scratch memory from ws. enc_pk receives sid || msg1, the keypair
+/// closure writes pk and sk in place.
+#[allow(clippy::too_many_arguments)]
+pub fn pake_init_start_in<R,F>(ws: &mut PakeWorkspace, enc_pk: &mut [u8;KYBER_SYMBYTES+MSG1_LEN], pk: &mut [u8;KYBER_PUBLICKEYBYTES], sk: &mut [u8;KYBER_SECRETKEYBYTES], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R, keypair: F) -> Result<(), PakeError>
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&mut [u8;KYBER_PUBLICKEYBYTES],&mut [u8;KYBER_SECRETKEYBYTES],&mut R) -> Result<(), PakeError>
+{
+    let mut sid = [0u8;KYBER_SYMBYTES];
+    randombytes(&mut sid, KYBER_SYMBYTES, rng)?;
+    pake_init_start_with_sid_in(ws, enc_pk, pk, sk, pw, &sid, channel_binding, rng, keypair)
+}
+
+/// Like pake_init_start_with_sid, with scratch memory from ws. See pake_init_start_in.
+#[allow(clippy::too_many_arguments)]
+pub fn pake_init_start_with_sid_in<R,F>(ws: &mut PakeWorkspace, enc_pk: &mut [u8;KYBER_SYMBYTES+MSG1_LEN], pk: &mut [u8;KYBER_PUBLICKEYBYTES], sk: &mut [u8;KYBER_SECRETKEYBYTES], pw: &[u8;KYBER_SYMBYTES], sid: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R, keypair: F) -> Result<(), PakeError>
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&mut [u8;KYBER_PUBLICKEYBYTES],&mut [u8;KYBER_SECRETKEYBYTES],&mut R) -> Result<(), PakeError>
+{
+    let (sid_out, msg1) = enc_pk.split_at_mut(KYBER_SYMBYTES);
+    sid_out.copy_from_slice(sid);
+    let msg1: &mut [u8;MSG1_LEN] = msg1.try_into().map_err(|_| PakeError::MalformedMessage)?;
+    init_start_in(ws, msg1, pk, sk, pw, &bind_sid(sid, channel_binding), rng, keypair)
+}
+
+/// Like pake_resp, with scratch memory from ws. msg2, ss and init_tag receive what pake_resp
+/// returns; the encapsulation closure writes the ciphertext and the shared secret in place.
+#[allow(clippy::too_many_arguments)]
+pub fn pake_resp_in<R,F>(ws: &mut PakeWorkspace, msg2: &mut [u8;MSG2_LEN], ss: &mut [u8;KYBER_SYMBYTES], init_tag: &mut [u8;KYBER_SYMBYTES], sid: &[u8;KYBER_SYMBYTES], msg1: &[u8;MSG1_LEN], pw: &[u8;KYBER_SYMBYTES], channel_binding: &[u8], rng: &mut R, encapsulate: F) -> Result<(), PakeError>
+where
+    R: CryptoRng + RngCore,
+    F: FnMut(&mut [u8;KYBER_CIPHERTEXTBYTES],&mut [u8;KYBER_SYMBYTES],&[u8;KYBER_PUBLICKEYBYTES],&mut R) -> Result<(), PakeError>
+{
+    resp_in(ws, ss, msg2, init_tag, msg1, pw, &bind_sid(sid, channel_binding), rng, encapsulate)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use crate::api::*;
+    use super::*;
+
+    // KEM stand-in that does no work of its own, so that only the protocol is measured: the
+    // ciphertext is random and its first KYBER_SYMBYTES bytes are the shared secret
+    fn keypair_in(pk: &mut [u8;KYBER_PUBLICKEYBYTES], sk: &mut [u8;KYBER_SECRETKEYBYTES], rng: &mut StdRng) -> Result<(), PakeError> {
+        pk.fill(0);
+        rng.fill_bytes(&mut pk[KYBER_PUBLICKEYBYTES-KYBER_SYMBYTES..]);
+        sk.fill(0);
+        Ok(())
+    }
+
+    fn encapsulate_in(ct: &mut [u8;KYBER_CIPHERTEXTBYTES], ss: &mut [u8;KYBER_SYMBYTES], _pk: &[u8;KYBER_PUBLICKEYBYTES], rng: &mut StdRng) -> Result<(), PakeError> {
+        rng.fill_bytes(ct);
+        ss.copy_from_slice(&ct[..KYBER_SYMBYTES]);
+        Ok(())
+    }
+
+    fn decapsulate(ct: &[u8;KYBER_CIPHERTEXTBYTES], _sk: &[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError> {
+        let mut ss = [0u8;KYBER_SYMBYTES];
+        ss.copy_from_slice(&ct[..KYBER_SYMBYTES]);
+        Ok(ss)
+    }
+
+    #[test]
+    fn test_workspace_matches_default_api() {
+        let mut ws = PakeWorkspace::new();
+        let pw = [3u8;KYBER_SYMBYTES];
+        let mut enc_pk = [0u8;KYBER_SYMBYTES+MSG1_LEN];
+        let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
+        let mut sk = [0u8;KYBER_SECRETKEYBYTES];
+        pake_init_start_in(&mut ws, &mut enc_pk, &mut pk, &mut sk, &pw, b"cb", &mut StdRng::seed_from_u64(1), keypair_in).unwrap();
+
+        // Same msg1 as the default entry point with the same randomness
+        let keypair = |rng: &mut StdRng| {
+            let (mut pk, mut sk) = ([0u8;KYBER_PUBLICKEYBYTES], [0u8;KYBER_SECRETKEYBYTES]);
+            keypair_in(&mut pk, &mut sk, rng)?;
+            Ok((pk, sk))
+        };
+        assert_eq!(pake_init_start_bound(&pw, b"cb", &mut StdRng::seed_from_u64(1), keypair).unwrap().0, enc_pk);
+
+        let (sid, msg1) = pake_split_msg1(&enc_pk);
+        let (mut msg2, mut ss, mut init_tag) = ([0u8;MSG2_LEN], [0u8;KYBER_SYMBYTES], [0u8;KYBER_SYMBYTES]);
+        pake_resp_in(&mut ws, &mut msg2, &mut ss, &mut init_tag, &sid, &msg1, &pw, b"cb", &mut StdRng::seed_from_u64(2), encapsulate_in).unwrap();
+
+        let encapsulate = |pk: &[u8;KYBER_PUBLICKEYBYTES], rng: &mut StdRng| {
+            let (mut ct, mut ss) = ([0u8;KYBER_CIPHERTEXTBYTES], [0u8;KYBER_SYMBYTES]);
+            encapsulate_in(&mut ct, &mut ss, pk, rng)?;
+            Ok((ct, ss))
+        };
+        assert_eq!(pake_resp_bound(&sid, &msg1, &pw, b"cb", &mut StdRng::seed_from_u64(2), encapsulate), Ok((msg2, ss, init_tag)));
+        assert_eq!(pake_init_end_bound(&msg2, &sid, &msg1, &pk, &sk, b"cb", decapsulate), Ok((ss, init_tag)));
+    }
+
+    #[cfg(feature = "std")]
+    mod stack {
+
+        use std::hint::black_box;
+        use super::*;
+
+        const STACK_PAINT_BYTES: usize = 64 * 1024;
+        const STACK_PAINT: u8 = 0xa5;
+
+        /// Fills STACK_PAINT_BYTES of fresh stack below the caller and returns their lowest address
+        #[inline(never)]
+        fn paint_stack() -> usize {
+            let mut painted = [STACK_PAINT; STACK_PAINT_BYTES];
+            black_box(&mut painted);
+            painted.as_ptr() as usize
+        }
+
+        /// Number of painted bytes that were overwritten since paint_stack
+        #[inline(never)]
+        fn painted_bytes_used(low: usize) -> usize {
+            let low = low as *const u8;
+            let mut untouched = 0;
+            // SAFETY: the painted region lies within this thread's stack, below the current
+            // frame, and is only read
+            while untouched < STACK_PAINT_BYTES && unsafe { core::ptr::read_volatile(low.add(untouched)) } == STACK_PAINT {
+                untouched += 1;
+            }
+            STACK_PAINT_BYTES - untouched
+        }
+
+        /// Peak stack usage of f, in bytes, measured in a fresh thread
+        fn stack_usage<F: FnOnce() + Send>(f: F) -> usize {
+            std::thread::scope(|s| {
+                std::thread::Builder::new().stack_size(4 * STACK_PAINT_BYTES).spawn_scoped(s, || {
+                    let low = paint_stack();
+                    f();
+                    painted_bytes_used(low)
+                }).unwrap().join().unwrap()
+            })
+        }
+
+        #[test]
+        #[cfg_attr(debug_assertions, ignore = "the bounds hold for optimized builds, run with --release")]
+        fn test_stack_bounds() {
+            let mut ws = PakeWorkspace::new();
+            let pw = [3u8;KYBER_SYMBYTES];
+            let mut enc_pk = [0u8;KYBER_SYMBYTES+MSG1_LEN];
+            let mut pk = [0u8;KYBER_PUBLICKEYBYTES];
+            let mut sk = [0u8;KYBER_SECRETKEYBYTES];
+            let mut msg2 = [0u8;MSG2_LEN];
+            let (mut ss, mut init_tag) = ([0u8;KYBER_SYMBYTES], [0u8;KYBER_SYMBYTES]);
+
+            let init_start = stack_usage(|| {
+                pake_init_start_in(&mut ws, &mut enc_pk, &mut pk, &mut sk, &pw, b"cb", &mut StdRng::seed_from_u64(1), keypair_in).unwrap();
+            });
+            let (sid, msg1) = pake_split_msg1(&enc_pk);
+            let resp = stack_usage(|| {
+                pake_resp_in(&mut ws, &mut msg2, &mut ss, &mut init_tag, &sid, &msg1, &pw, b"cb", &mut StdRng::seed_from_u64(2), encapsulate_in).unwrap();
+            });
+            let init_end = stack_usage(|| {
+                black_box(pake_init_end_bound(&msg2, &sid, &msg1, &pk, &sk, b"cb", decapsulate).unwrap());
+            });
+            let init_end_implicit = stack_usage(|| {
+                black_box(pake_init_end_implicit_bound(&msg2, &sid, &msg1, &pk, &sk, b"cb", &[5u8;KYBER_SYMBYTES], decapsulate).unwrap());
+            });
+
+            assert!(init_start <= PAKE_INIT_START_STACK_MAX, "pake_init_start_in uses {} bytes of stack", init_start);
+            assert!(resp <= PAKE_RESP_STACK_MAX, "pake_resp_in uses {} bytes of stack", resp);
+            assert!(init_end <= PAKE_INIT_END_STACK_MAX, "pake_init_end uses {} bytes of stack", init_end);
+            assert!(init_end_implicit <= PAKE_INIT_END_STACK_MAX, "pake_init_end_implicit uses {} bytes of stack", init_end_implicit);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! End-to-end password change: log in, change the password, then only the new one works.
+#![cfg(feature = "std")]
+
+use chic_rust::*;
+use rand::rngs::ThreadRng;
+
+fn keypair_func(rng: &mut ThreadRng) -> Result<([u8;KYBER_PUBLICKEYBYTES],[u8;KYBER_SECRETKEYBYTES]), PakeError> {
+    let keypair = pqc_kyber::keypair(rng).unwrap();
+    Ok((keypair.public, keypair.secret))
+}
+
+fn encapsulate_func(pk: &[u8;KYBER_PUBLICKEYBYTES], rng: &mut ThreadRng) -> Result<([u8;KYBER_CIPHERTEXTBYTES],[u8;KYBER_SYMBYTES]), PakeError> {
+    Ok(pqc_kyber::encapsulate(pk, rng).unwrap())
+}
+
+fn decapsulate_func(ct: &[u8;KYBER_CIPHERTEXTBYTES], sk: &[u8;KYBER_SECRETKEYBYTES]) -> Result<[u8;KYBER_SYMBYTES], PakeError> {
+    Ok(pqc_kyber::decapsulate(ct, sk).unwrap())
+}
+
+/// Logs in and returns the keys of the client and of the server
+fn login<S: CredentialStore>(store: &S, password: &[u8]) -> Result<([u8;KYBER_SYMBYTES],[u8;KYBER_SYMBYTES]), PakeError> {
+    let mut rng = rand::thread_rng();
+    let config = LoginServerConfig {
+        fake_secret: [9u8;KYBER_SYMBYTES],
+        fake_algorithm: PasswordHashAlgorithm::Raw,
+        fake_params: HashParams::default(),
+    };
+    let client_config = LoginClientConfig { algorithms: &[PasswordHashAlgorithm::Raw], ..LoginClientConfig::default() };
+    let hello = login_client_hello(b"alice", &mut rng)?;
+    let (server_hello, server) = login_server_hello(store, &config, &hello, &mut rng)?;
+    let (msg1, client) = login_client_msg1(&client_config, &hello, &server_hello, password, &[], &mut rng, keypair_func)?;
+    let (msg2, key_s, expected_tag) = login_server_msg2(&server, &msg1, &[], &mut rng, encapsulate_func)?;
+    let (key_c, tag) = login_client_finish(&client, &msg2, &[], decapsulate_func)?;
+    pake_resp_confirm(&expected_tag, &tag)?;
+    Ok((key_c, key_s))
+}
+
+#[test]
+fn test_password_change() {
+    let mut rng = rand::thread_rng();
+    let old_password = [1u8;KYBER_SYMBYTES];
+    let new_password = [2u8;KYBER_SYMBYTES];
+    let path = std::env::temp_dir().join(format!("chic-password-change-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut store = FileCredentialStore::open(&path).unwrap();
+    let record = CredentialRecord::new(&old_password, PasswordHashAlgorithm::Raw, &[3u8;SALT_BYTES], HashParams::default()).unwrap();
+    store.register(b"alice", record).unwrap();
+
+    let (key_c, key_s) = login(&store, &old_password).unwrap();
+    let new_record = CredentialRecord::new(&new_password, PasswordHashAlgorithm::Raw, &[4u8;SALT_BYTES], HashParams::default()).unwrap();
+    let request = password_change_request(&key_c, &new_record, &mut rng).unwrap();
+
+    // A request that was modified or made under another session is rejected and changes nothing
+    let mut tampered = request;
+    tampered[KYBER_SYMBYTES] ^= 1;
+    assert_eq!(password_change_apply(&mut store, b"alice", &key_s, &tampered), Err(PakeError::AuthenticationFailure));
+    assert_eq!(password_change_apply(&mut store, b"alice", &[0u8;KYBER_SYMBYTES], &request), Err(PakeError::AuthenticationFailure));
+    assert!(login(&store, &old_password).is_ok());
+
+    assert_eq!(password_change_apply(&mut store, b"alice", &key_s, &request), Ok(()));
+
+    // The change is persisted; only the new password works from now on
+    let store = FileCredentialStore::open(&path).unwrap();
+    assert_eq!(login(&store, &old_password), Err(PakeError::AuthenticationFailure));
+    assert!(login(&store, &new_password).is_ok());
+    std::fs::remove_file(&path).unwrap();
+}